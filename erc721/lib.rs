@@ -2,8 +2,141 @@
 
 use ink_lang as ink;
 
+use ink_env::{
+    AccountId,
+    DefaultEnvironment,
+    Environment,
+};
+use ink_prelude::{
+    string::String,
+    vec::Vec,
+};
+use scale::{
+    Decode,
+    Encode,
+};
+
+type Balance = <DefaultEnvironment as Environment>::Balance;
+
+/// Error returned by a receiver contract that rejects a token.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum ReceiverError {
+    TransferRejected,
+}
+
+/// Interface for contracts that accept tokens sent with `safe_transfer_from`.
+#[ink::trait_definition]
+pub trait Erc721Receiver {
+    /// Handles the receipt of token `id`, returns `Ok` to accept it.
+    #[ink(message, selector = 0x150B7A02)]
+    fn on_erc721_received(
+        &mut self,
+        operator: AccountId,
+        from: AccountId,
+        id: erc721::TokenId,
+        data: Vec<u8>,
+    ) -> Result<(), ReceiverError>;
+}
+
+/// Token id of the PSP34 standard, encoded like the OpenBrush `Id`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum Id {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    Bytes(Vec<u8>),
+}
+
+/// Error of the PSP34 standard, encoded like the OpenBrush `PSP34Error`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP34Error {
+    Custom(String),
+    SelfApprove,
+    NotApproved,
+    TokenExists,
+    TokenNotExists,
+    SafeTransferCheckFailed(String),
+}
+
+/// Messages of the PSP34 standard, with the standard selectors.
+#[ink::trait_definition]
+pub trait PSP34 {
+    /// Returns the collection id.
+    #[ink(message)]
+    fn collection_id(&self) -> Id;
+
+    /// Returns the number of tokens owned.
+    #[ink(message)]
+    fn balance_of(&self, owner: AccountId) -> u32;
+
+    /// Returns the owner of the token.
+    #[ink(message)]
+    fn owner_of(&self, id: Id) -> Option<AccountId>;
+
+    /// Returns true if `operator` may transfer token `id` of `owner`, or all
+    /// tokens of `owner` if `id` is `None`.
+    #[ink(message)]
+    fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<Id>) -> bool;
+
+    /// Approves or disapproves `operator` for token `id`, or for all tokens of
+    /// the caller if `id` is `None`.
+    #[ink(message)]
+    fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> Result<(), PSP34Error>;
+
+    /// Transfers token `id` from the caller to `to`.
+    #[ink(message)]
+    fn transfer(&mut self, to: AccountId, id: Id, data: Vec<u8>) -> Result<(), PSP34Error>;
+
+    /// Returns the number of live tokens.
+    #[ink(message)]
+    fn total_supply(&self) -> Balance;
+}
+
+/// Error returned by a PSP34 receiver contract, encoded like the OpenBrush
+/// `PSP34ReceiverError`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP34ReceiverError {
+    TransferRejected(String),
+}
+
+/// Interface for contracts that accept tokens sent with `PSP34::transfer`.
+#[ink::trait_definition]
+pub trait PSP34Receiver {
+    /// Handles the receipt of token `id`, returns `Ok` to accept it.
+    #[ink(message, selector = 0xBB7DF780)]
+    fn before_received(
+        &mut self,
+        operator: AccountId,
+        from: AccountId,
+        id: Id,
+        data: Vec<u8>,
+    ) -> Result<(), PSP34ReceiverError>;
+}
+
 #[ink::contract]
 mod erc721 {
+    use crate::{
+        Id,
+        PSP34Error,
+        PSP34ReceiverError,
+        ReceiverError,
+        PSP34,
+    };
+    use ink_env::hash::{
+        Blake2x256,
+        Keccak256,
+    };
+    use ink_prelude::{
+        format,
+        string::String,
+        vec::Vec,
+    };
     use ink_storage::{
         traits::SpreadAllocate,
         Mapping,
@@ -14,21 +147,133 @@ mod erc721 {
         Encode,
     };
 
-    pub type TokenId = u32;
+    /// Token id, SCALE encoded in storage keys and values. Changing its width
+    /// changes the storage layout, which needs a `STORAGE_VERSION` bump and a
+    /// migration re-keying every token mapping.
+    pub type TokenId = u128;
+
+    /// Denominator of royalty fractions, in basis points.
+    pub const FEE_DENOMINATOR: u16 = 10_000;
+
+    /// Version of the storage layout, bumped whenever `migrate` has work to do.
+    /// Version 1 is the first released layout, with `u128` token ids.
+    pub const STORAGE_VERSION: u32 = 1;
+
+    /// Maximum number of attributes of a single token.
+    pub const MAX_ATTRIBUTES: u32 = 32;
+
+    /// Maximum number of tokens handled by a single batch message.
+    pub const MAX_BATCH_LENGTH: u32 = 50;
+
+    /// Maximum number of items handled by a single bulk query.
+    pub const MAX_QUERY_LENGTH: u32 = 1_000;
+
+    /// Domain separator of the messages signed for `permit`.
+    pub const PERMIT_DOMAIN: [u8; 14] = *b"erc721::permit";
 
     #[ink(storage)]
     #[derive(Default, SpreadAllocate)]
     pub struct Erc721 {
         name: String,
         symbol: String,
-        /// Total token supply, set at init
-        total_supply: u32,
-        /// Mint count
+        /// Contract owner, set at init
+        owner: AccountId,
+        /// Whether any account can mint, set at init
+        public_mint: bool,
+        /// Whether transfers, mints and burns are stopped
+        paused: bool,
+        /// Base URI of the token metadata
+        base_uri: String,
+        /// Mapping from token to its URI, overrides the base URI
+        token_uris: Mapping<TokenId, String>,
+        /// Default royalty receiver and fraction in basis points
+        default_royalty: (AccountId, u16),
+        /// Mapping from token to its royalty, overrides the default royalty
+        token_royalties: Mapping<TokenId, (AccountId, u16)>,
+        /// Maximum token supply, set at init
+        max_supply: u32,
+        /// Mint count, burned tokens included
         count: u32,
         /// Mapping from token to owner, 1-1
         token_owners: Mapping<TokenId, AccountId>,
         /// Mapping from owner to token count
         balances: Mapping<AccountId, u32>,
+        /// Mapping from token to approved account
+        token_approvals: Mapping<TokenId, AccountId>,
+        /// Mapping from owner to operator approvals
+        operator_approvals: Mapping<(AccountId, AccountId), ()>,
+        /// Mapping from (owner, index) to token, for owner enumeration
+        owned_tokens: Mapping<(AccountId, u32), TokenId>,
+        /// Mapping from token to its index in the owner's list
+        owned_tokens_index: Mapping<TokenId, u32>,
+        /// Mapping from global index to token, for token enumeration
+        all_tokens: Mapping<u32, TokenId>,
+        /// Mapping from token to its index in the global list
+        all_tokens_index: Mapping<TokenId, u32>,
+        /// Number of tokens in the global list, i.e. live tokens
+        all_tokens_count: u32,
+        /// Version of the storage layout, new fields must be appended after it
+        storage_version: u32,
+        /// Hash of the reveal seed committed at init, `None` if revealed at mint
+        provenance_hash: Option<[u8; 32]>,
+        /// Whether the metadata has been revealed
+        revealed: bool,
+        /// Offset from token id to metadata id, derived from the reveal seed
+        token_offset: u32,
+        /// Price of a public mint
+        mint_price: Balance,
+        /// Start of the public sale, 0 for no start
+        sale_start: Timestamp,
+        /// End of the public sale, 0 for no end
+        sale_end: Timestamp,
+        /// Maximum public mints per account, 0 for no limit
+        wallet_limit: u32,
+        /// Mapping from account to its public mint count
+        minted_per_account: Mapping<AccountId, u32>,
+        /// Merkle root of the presale allowlist, `None` outside the presale
+        merkle_root: Option<[u8; 32]>,
+        /// Mapping from account to its presale mint count
+        presale_claimed: Mapping<AccountId, u32>,
+        /// Whether tokens are bound to their owner, set at init
+        soulbound: bool,
+        /// Mapping from token to its user and the expiry of the user role
+        users: Mapping<TokenId, (AccountId, Timestamp)>,
+        /// Whether token owners, rather than the contract owner, set attributes
+        token_owner_attributes: bool,
+        /// Mapping from (token, key) to attribute value
+        attributes: Mapping<(TokenId, Vec<u8>), Vec<u8>>,
+        /// Mapping from token to its attribute keys
+        attribute_keys: Mapping<TokenId, Vec<Vec<u8>>>,
+        /// Last id assigned by sequential mints
+        last_sequential_id: TokenId,
+        /// Mapping from owner to the nonce of its next permit
+        permit_nonces: Mapping<AccountId, u64>,
+        /// Mapping from token to the account that locked it and the lock expiry
+        token_locks: Mapping<TokenId, (AccountId, Timestamp)>,
+        /// Mapping from (account, index) to a block and the balance at its end
+        balance_checkpoints: Mapping<(AccountId, u32), (BlockNumber, u32)>,
+        /// Mapping from account to its number of balance checkpoints
+        balance_checkpoint_counts: Mapping<AccountId, u32>,
+        /// Mapping from account to the delegate of its votes
+        delegates: Mapping<AccountId, AccountId>,
+        /// Mapping from (delegate, index) to a block and the votes at its end
+        vote_checkpoints: Mapping<(AccountId, u32), (BlockNumber, u32)>,
+        /// Mapping from delegate to its number of vote checkpoints
+        vote_checkpoint_counts: Mapping<AccountId, u32>,
+    }
+
+    /// Optional modes of a collection, set once by the constructor.
+    #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Config {
+        /// Whether any account can mint, otherwise only the contract owner
+        pub public_mint: bool,
+        /// Default royalty receiver and fraction in basis points
+        pub royalty: (AccountId, u16),
+        /// Provenance hash of the metadata, which stays hidden until `reveal`
+        pub provenance_hash: Option<[u8; 32]>,
+        /// Whether tokens can only be minted and burned, never transferred
+        pub soulbound: bool,
     }
 
     #[derive(Encode, Decode, Debug, PartialEq, Eq, Copy, Clone)]
@@ -41,6 +286,34 @@ mod erc721 {
         CannotInsert,
         CannotFetchValue,
         NotAllowed,
+        NotApproved,
+        SafeTransferCheckFailed,
+        NotContractOwner,
+        MintNotAllowed,
+        BatchTooLarge,
+        RoyaltyTooHigh,
+        Paused,
+        NotPaused,
+        UpgradeFailed,
+        AlreadyMigrated,
+        AlreadyRevealed,
+        InvalidSeed,
+        InvalidPayment,
+        SaleNotActive,
+        WalletLimitReached,
+        InsufficientBalance,
+        TransferFailed,
+        PresaleNotActive,
+        InvalidProof,
+        NonTransferable,
+        TooManyAttributes,
+        PermitExpired,
+        InvalidSignature,
+        QueryTooLarge,
+        TokenLocked,
+        MetadataFrozen,
+        IdReserved,
+        BlockNotFinished,
     }
 
     #[ink(event)]
@@ -53,20 +326,196 @@ mod erc721 {
         id: TokenId,
     }
 
+    /// Event emitted when a token approve occurs.
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        id: TokenId,
+    }
+
+    /// Event emitted when an operator is enabled or disabled for an owner.
+    #[ink(event)]
+    pub struct ApprovalForAll {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        approved: bool,
+    }
+
+    /// Event emitted when the contract ownership is transferred.
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: Option<AccountId>,
+        #[ink(topic)]
+        new_owner: Option<AccountId>,
+    }
+
+    /// Event emitted when the base URI is updated.
+    #[ink(event)]
+    pub struct BaseUriUpdated {
+        base_uri: String,
+    }
+
+    /// Event emitted when the default royalty or the royalty of a token is updated.
+    #[ink(event)]
+    pub struct RoyaltyUpdated {
+        #[ink(topic)]
+        id: Option<TokenId>,
+        #[ink(topic)]
+        receiver: AccountId,
+        fraction: u16,
+    }
+
+    /// Event emitted when the contract is paused.
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when the contract is unpaused.
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when the contract code is upgraded.
+    #[ink(event)]
+    pub struct Upgraded {
+        #[ink(topic)]
+        code_hash: [u8; 32],
+    }
+
+    /// Event emitted when the storage is migrated to a new version.
+    #[ink(event)]
+    pub struct Migrated {
+        from_version: u32,
+        to_version: u32,
+    }
+
+    /// Event emitted when the metadata is revealed.
+    #[ink(event)]
+    pub struct Revealed {
+        base_uri: String,
+        token_offset: u32,
+    }
+
+    /// Event emitted when the public sale is updated.
+    #[ink(event)]
+    pub struct SaleUpdated {
+        price: Balance,
+        start: Timestamp,
+        end: Timestamp,
+        wallet_limit: u32,
+    }
+
+    /// Event emitted when the presale Merkle root is updated.
+    #[ink(event)]
+    pub struct MerkleRootUpdated {
+        merkle_root: Option<[u8; 32]>,
+    }
+
+    /// Event emitted when the user of a token is set or cleared.
+    #[ink(event)]
+    pub struct UpdateUser {
+        #[ink(topic)]
+        id: TokenId,
+        #[ink(topic)]
+        user: Option<AccountId>,
+        expires: Timestamp,
+    }
+
+    /// Event emitted when a token is locked.
+    #[ink(event)]
+    pub struct Locked {
+        #[ink(topic)]
+        id: TokenId,
+        #[ink(topic)]
+        locker: AccountId,
+        until: Timestamp,
+    }
+
+    /// Event emitted when a token is unlocked before its lock expires.
+    #[ink(event)]
+    pub struct Unlocked {
+        #[ink(topic)]
+        id: TokenId,
+    }
+
+    /// Event emitted when an account changes its delegate.
+    #[ink(event)]
+    pub struct DelegateChanged {
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        from_delegate: Option<AccountId>,
+        #[ink(topic)]
+        to_delegate: AccountId,
+    }
+
+    /// Event emitted when the votes of a delegate change.
+    #[ink(event)]
+    pub struct DelegateVotesChanged {
+        #[ink(topic)]
+        delegate: AccountId,
+        previous_votes: u32,
+        new_votes: u32,
+    }
+
+    /// Event emitted when an attribute of a token is set.
+    #[ink(event)]
+    pub struct AttributeSet {
+        #[ink(topic)]
+        id: TokenId,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    }
+
     impl Erc721 {
-        /// Constructor that initializes the `bool` value to the given `init_value`.
+        /// Constructor that initializes the token with the caller as contract owner.
+        /// With a provenance hash in `config`, `base_uri` is a placeholder until
+        /// `reveal`.
         #[ink(constructor)]
-        pub fn new(name: String, symbol: String, init_supply: u32) -> Self {
+        pub fn new(
+            name: String,
+            symbol: String,
+            init_supply: u32,
+            base_uri: String,
+            config: Config,
+        ) -> Self {
+            assert!(config.royalty.1 <= FEE_DENOMINATOR, "royalty fraction above 100%");
             ink_lang::utils::initialize_contract(|contract| {
-                Self::new_init(contract, name, symbol, init_supply)
+                Self::new_init(contract, name, symbol, init_supply, base_uri, config)
             })
         }
 
-        fn new_init(&mut self, name: String, symbol: String, init_supply: u32) {
+        fn new_init(
+            &mut self,
+            name: String,
+            symbol: String,
+            init_supply: u32,
+            base_uri: String,
+            config: Config,
+        ) {
             self.name = name;
             self.symbol = symbol;
-            self.total_supply = init_supply;
+            self.owner = self.env().caller();
+            self.public_mint = config.public_mint;
+            self.base_uri = base_uri;
+            self.default_royalty = config.royalty;
+            self.max_supply = init_supply;
             self.count = 0;
+            self.storage_version = STORAGE_VERSION;
+            self.provenance_hash = config.provenance_hash;
+            self.revealed = config.provenance_hash.is_none();
+            self.soulbound = config.soulbound;
         }
 
         /// Returns the name of the token.
@@ -81,266 +530,3063 @@ mod erc721 {
             self.symbol.to_string()
         }
 
-        /// Returns the total supply of the token.
+        /// Returns the contract owner.
         #[ink(message)]
-        pub fn total_supply(&self) -> u32 {
-            self.total_supply
+        pub fn owner(&self) -> AccountId {
+            self.owner
         }
 
-        /// Returns the owner of the token.
+        /// Returns true if any account can mint.
         #[ink(message)]
-        pub fn owner_of(&self, id: TokenId) -> Option<AccountId> {
-            self.token_owners.get(&id)
+        pub fn public_mint(&self) -> bool {
+            self.public_mint
         }
 
-        /// Returns the number of tokens owned.
+        /// Transfers the contract ownership to `new_owner`, only callable by the contract owner.
         #[ink(message)]
-        pub fn balance_of(&self, owner: AccountId) -> u32 {
-            self.balance_of_or_zero(&owner)
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if new_owner == AccountId::from([0x0; 32]) {
+                return Err(Error::NotAllowed)
+            }
+
+            let previous_owner = self.owner;
+            self.owner = new_owner;
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner: Some(previous_owner),
+                new_owner: Some(new_owner),
+            });
+            Ok(())
         }
 
-        /// Transfers the token from the caller to the given address.
+        /// Leaves the contract without owner, only callable by the contract owner.
         #[ink(message)]
-        pub fn transfer(
-            &mut self,
-            destination: AccountId,
-            id: TokenId,
-        ) -> Result<(), Error> {
-            let caller = self.env().caller();
-            self.transfer_token_from(&caller, &destination, id)?;
+        pub fn renounce_ownership(&mut self) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            let previous_owner = self.owner;
+            self.owner = AccountId::from([0x0; 32]);
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner: Some(previous_owner),
+                new_owner: None,
+            });
             Ok(())
         }
 
-        /// Creates a new token.
+        /// Returns true if transfers, mints and burns are stopped.
         #[ink(message)]
-        pub fn mint(&mut self) -> Result<(), Error> {
-            if self.count == self.total_supply {
-                return Err(Error::TokenSupplyLimit)
-            }
+        pub fn paused(&self) -> bool {
+            self.paused
+        }
 
-            let caller = self.env().caller();
-            if caller == AccountId::from([0x0; 32]) {
-                return Err(Error::NotAllowed)
+        /// Stops transfers, mints and burns, only callable by the contract owner.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            self.ensure_not_paused()?;
+            self.paused = true;
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Resumes transfers, mints and burns, only callable by the contract owner.
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if !self.paused {
+                return Err(Error::NotPaused)
             }
 
-            self.count=self.count+1;
-            let id = self.count;
-            
-            self.add_token_to(&caller, id)?;
-            self.env().emit_event(Transfer {
-                from: Some(AccountId::from([0x0; 32])),
-                to: Some(caller),
-                id,
+            self.paused = false;
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
             });
             Ok(())
         }
 
-        /// Destroys a token forever.
+        /// Returns the version of the storage layout.
         #[ink(message)]
-        pub fn burn(&mut self, id: TokenId) -> Result<(), Error> {
-            let caller = self.env().caller();
-            self.remove_token_from(&caller, id)?;
-            self.env().emit_event(Transfer { 
-                from: Some(caller),
-                to: Some(AccountId::from([0x0; 32])),
-                id,
-            });
+        pub fn storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        /// Replaces the contract code, only callable by the contract owner.
+        /// The new code must keep the storage layout and then run `migrate`.
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: [u8; 32]) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            ink_env::set_code_hash(&code_hash).map_err(|_| Error::UpgradeFailed)?;
+            self.env().emit_event(Upgraded { code_hash });
             Ok(())
         }
 
-        /*
-        /// WIP
-        /// Returns a vector of TokenId's owned by an address.
+        /// Migrates the storage to `STORAGE_VERSION`, only callable by the
+        /// contract owner and once per version.
         #[ink(message)]
-        pub fn tokens_of_owner(&mut self, of: AccountId) -> Vec<AccountId> {
-             // calls balanceOf
-             let result: Vec<AccountId> = vec![];
-             result
+        pub fn migrate(&mut self) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            let from_version = self.storage_version;
+            if from_version >= STORAGE_VERSION {
+                return Err(Error::AlreadyMigrated)
+            }
+
+            // Migration steps of each version go here, e.g.
+            // `if from_version < 2 { ... }`
+            self.storage_version = STORAGE_VERSION;
+            self.env().emit_event(Migrated {
+                from_version,
+                to_version: STORAGE_VERSION,
+            });
+            Ok(())
         }
-        */
 
-        /// Transfers token `id` `from` the sender to the `to` `AccountId`
-        fn transfer_token_from(
-            &mut self,
-            from: &AccountId,
-            to: &AccountId,
-            id: TokenId,
-        ) -> Result<(), Error> {
+        /// Returns true if token `id` cannot be transferred, `None` if it does not exist.
+        #[ink(message)]
+        pub fn locked(&self, id: TokenId) -> Option<bool> {
             if !self.exists(id) {
-                return Err(Error::TokenNotFound)
+                return None
             }
+            Some(self.soulbound || self.is_locked(id))
+        }
 
-            self.remove_token_from(from, id)?;
-            self.add_token_to(to, id)?;
+        /// Locks token `id` until `until`, blocking its transfers and burns while
+        /// it stays with its owner, e.g. while staked. The caller must be the token
+        /// owner or an operator of the owner, and only the caller can unlock it or
+        /// extend the lock.
+        #[ink(message)]
+        pub fn lock(&mut self, id: TokenId, until: Timestamp) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let owner = self.owner_of(id).ok_or(Error::TokenNotFound)?;
+            // Accounts approved for the token alone could lock it for good
+            if !(owner == caller || self.approved_for_all(owner, caller)) {
+                return Err(Error::NotApproved)
+            }
 
-            self.env().emit_event(Transfer {
-                from: Some(*from),
-                to: Some(*to),
-                id: id,
+            if until <= self.env().block_timestamp() {
+                return Err(Error::NotAllowed)
+            }
+
+            if self.is_locked(id) && self.token_locks.get(id).map(|(locker, _)| locker) != Some(caller) {
+                return Err(Error::TokenLocked)
+            }
+
+            self.token_locks.insert(id, &(caller, until));
+            self.env().emit_event(Locked {
+                id,
+                locker: caller,
+                until,
             });
             Ok(())
         }
 
-        /// Removes token `id` from the owner.
-        fn remove_token_from(
-            &mut self,
-            from: &AccountId,
-            id: TokenId,
-        ) -> Result<(), Error> {
-            let owner = self.token_owners.get(&id);
-            if owner.is_none() {
-                return Err(Error::TokenNotFound)
+        /// Unlocks token `id` before its lock expires, only callable by the
+        /// account that locked it.
+        #[ink(message)]
+        pub fn unlock(&mut self, id: TokenId) -> Result<(), Error> {
+            if !self.is_locked(id) {
+                return Err(Error::NotAllowed)
             }
 
-            if owner != Some(*from) {
-                return Err(Error::NotOwner)
+            if self.token_locks.get(id).map(|(locker, _)| locker) != Some(self.env().caller()) {
+                return Err(Error::NotApproved)
             }
 
-            let count = self.balances
-                .get(&from)
-                .map(|c| c - 1)
-                .ok_or(Error::CannotFetchValue)?;
-            self.balances.insert(&from, &count);
-            self.token_owners.remove(&id);
+            self.token_locks.remove(id);
+            self.env().emit_event(Unlocked { id });
             Ok(())
         }
 
-        /// Adds the token `id` to the `to` AccountId.
-        fn add_token_to(
-            &mut self,
-            to: &AccountId,
-            id: TokenId,
-        ) -> Result<(), Error> {
-            if self.token_owners.get(&id).is_some() {
-                return Err(Error::TokenExists)
+        /// Returns true if token `id` is locked and the lock has not expired.
+        #[ink(message)]
+        pub fn is_locked(&self, id: TokenId) -> bool {
+            self.token_locks
+                .get(id)
+                .map_or(false, |(_, until)| until >= self.env().block_timestamp())
+        }
+
+        /// Sets the user of token `id` until `expires`, the zero address clears it.
+        /// The caller must be the token owner or approved.
+        #[ink(message)]
+        pub fn set_user(&mut self, id: TokenId, user: AccountId, expires: Timestamp) -> Result<(), Error> {
+            if !self.exists(id) {
+                return Err(Error::TokenNotFound)
             }
 
-            if *to == AccountId::from([0x0; 32]) {
-                return Err(Error::NotAllowed)         
+            if !self.approved_or_owner(self.env().caller(), id) {
+                return Err(Error::NotApproved)
             }
 
-            let count = self.balances
-                .get(to)
-                .map(|c| c + 1)
-                .unwrap_or(1);
-            self.balances.insert(to, &count);
-            self.token_owners.insert(&id, to);
+            if user == AccountId::from([0x0; 32]) {
+                self.clear_user(id);
+                return Ok(())
+            }
+
+            self.users.insert(id, &(user, expires));
+            self.env().emit_event(UpdateUser {
+                id,
+                user: Some(user),
+                expires,
+            });
             Ok(())
         }
 
-        /// Returns true if token `id` exists or false if it does not.
-        fn exists(&self, id: TokenId) -> bool {
-            self.token_owners.get(&id).is_some()
+        /// Returns the user of token `id`, `None` if there is none or it expired.
+        #[ink(message)]
+        pub fn user_of(&self, id: TokenId) -> Option<AccountId> {
+            self.users
+                .get(id)
+                .filter(|(_, expires)| *expires >= self.env().block_timestamp())
+                .map(|(user, _)| user)
         }
 
-        /// Returns the total number of tokens from an account.
-        fn balance_of_or_zero(&self, of: &AccountId) -> u32 {
-            self.balances.get(of).unwrap_or(0)
+        /// Returns the expiry of the user role of token `id`, 0 if there is none.
+        #[ink(message)]
+        pub fn user_expires(&self, id: TokenId) -> Timestamp {
+            self.users.get(id).map(|(_, expires)| expires).unwrap_or(0)
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink_lang as ink;
+        /// Sets the attribute `key` of token `id` to `value`. The caller must be the
+        /// contract owner, or the token owner if token owners control attributes.
+        #[ink(message)]
+        pub fn set_attribute(&mut self, id: TokenId, key: Vec<u8>, value: Vec<u8>) -> Result<(), Error> {
+            let owner = self.owner_of(id).ok_or(Error::TokenNotFound)?;
+            if self.token_owner_attributes {
+                if self.env().caller() != owner {
+                    return Err(Error::NotOwner)
+                }
+            } else {
+                self.ensure_contract_owner()?;
+            }
 
-        #[ink::test]
-        fn init_works() {
-            // Create a new contract instance.
-            let erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 50);
-            // Check the token name
+            let mut keys = self.attribute_keys.get(id).unwrap_or_default();
+            if !keys.contains(&key) {
+                if keys.len() >= MAX_ATTRIBUTES as usize {
+                    return Err(Error::TooManyAttributes)
+                }
+                keys.push(key.clone());
+                self.attribute_keys.insert(id, &keys);
+            }
+
+            self.attributes.insert((id, &key), &value);
+            self.env().emit_event(AttributeSet { id, key, value });
+            Ok(())
+        }
+
+        /// Returns the attribute `key` of token `id`, if set.
+        #[ink(message)]
+        pub fn get_attribute(&self, id: TokenId, key: Vec<u8>) -> Option<Vec<u8>> {
+            self.attributes.get((id, key))
+        }
+
+        /// Returns true if token owners, rather than the contract owner, set attributes.
+        #[ink(message)]
+        pub fn token_owner_attributes(&self) -> bool {
+            self.token_owner_attributes
+        }
+
+        /// Lets token owners, rather than the contract owner, set attributes.
+        /// Only callable by the contract owner.
+        #[ink(message)]
+        pub fn set_token_owner_attributes(&mut self, enabled: bool) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            self.token_owner_attributes = enabled;
+            Ok(())
+        }
+
+        /// Returns the URI of token `id`, the token URI if set or else `base_uri + id`.
+        #[ink(message)]
+        pub fn token_uri(&self, id: TokenId) -> Option<String> {
+            if !self.exists(id) {
+                return None
+            }
+
+            if !self.revealed {
+                return Some(self.base_uri.clone())
+            }
+
+            Some(self.token_uris.get(id).unwrap_or_else(|| {
+                format!("{}{}", self.base_uri, self.metadata_id(id))
+            }))
+        }
+
+        /// Returns the provenance hash committed at init.
+        #[ink(message)]
+        pub fn provenance_hash(&self) -> Option<[u8; 32]> {
+            self.provenance_hash
+        }
+
+        /// Returns true if the metadata has been revealed.
+        #[ink(message)]
+        pub fn revealed(&self) -> bool {
+            self.revealed
+        }
+
+        /// Reveals the metadata at `base_uri`, only callable by the contract owner.
+        /// The Blake2x256 hash of `seed` must match the provenance hash, the token
+        /// offset is then derived from the Keccak256 hash of `seed`, so it stays
+        /// hidden until the reveal.
+        #[ink(message)]
+        pub fn reveal(&mut self, base_uri: String, seed: Vec<u8>) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if self.revealed {
+                return Err(Error::AlreadyRevealed)
+            }
+
+            let mut hash = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&seed, &mut hash);
+            if self.provenance_hash != Some(hash) {
+                return Err(Error::InvalidSeed)
+            }
+
+            let mut offset = [0u8; 32];
+            ink_env::hash_bytes::<Keccak256>(&seed, &mut offset);
+            let offset = u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]);
+            self.token_offset = offset.checked_rem(self.max_supply).unwrap_or(0);
+            self.base_uri = base_uri.clone();
+            self.revealed = true;
+            self.env().emit_event(Revealed {
+                base_uri,
+                token_offset: self.token_offset,
+            });
+            Ok(())
+        }
+
+        /// Returns the base URI of the token metadata.
+        #[ink(message)]
+        pub fn base_uri(&self) -> String {
+            self.base_uri.clone()
+        }
+
+        /// Updates the base URI, only callable by the contract owner. The revealed
+        /// base URI of a collection with a provenance hash cannot be changed.
+        #[ink(message)]
+        pub fn set_base_uri(&mut self, base_uri: String) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if self.provenance_hash.is_some() && self.revealed {
+                return Err(Error::MetadataFrozen)
+            }
+
+            self.base_uri = base_uri.clone();
+            self.env().emit_event(BaseUriUpdated { base_uri });
+            Ok(())
+        }
+
+        /// Sets the URI of token `id`, only callable by the contract owner.
+        /// Collections with a provenance hash have no per-token URIs, as they
+        /// would bypass the committed metadata.
+        #[ink(message)]
+        pub fn set_token_uri(&mut self, id: TokenId, uri: String) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if self.provenance_hash.is_some() {
+                return Err(Error::MetadataFrozen)
+            }
+
+            if !self.exists(id) {
+                return Err(Error::TokenNotFound)
+            }
+
+            self.token_uris.insert(id, &uri);
+            Ok(())
+        }
+
+        /// Returns the royalty receiver of token `id` and the royalty amount owed
+        /// for `sale_price`.
+        #[ink(message)]
+        pub fn royalty_info(&self, id: TokenId, sale_price: Balance) -> (AccountId, Balance) {
+            let (receiver, fraction) = self.token_royalties.get(id).unwrap_or(self.default_royalty);
+            let denominator = FEE_DENOMINATOR as Balance;
+            let fraction = fraction as Balance;
+            // Split the price to avoid overflowing on large sale prices
+            let amount = sale_price / denominator * fraction
+                + sale_price % denominator * fraction / denominator;
+            (receiver, amount)
+        }
+
+        /// Sets the default royalty, only callable by the contract owner.
+        #[ink(message)]
+        pub fn set_default_royalty(&mut self, receiver: AccountId, fraction: u16) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if fraction > FEE_DENOMINATOR {
+                return Err(Error::RoyaltyTooHigh)
+            }
+
+            self.default_royalty = (receiver, fraction);
+            self.env().emit_event(RoyaltyUpdated {
+                id: None,
+                receiver,
+                fraction,
+            });
+            Ok(())
+        }
+
+        /// Sets the royalty of token `id`, only callable by the contract owner.
+        #[ink(message)]
+        pub fn set_token_royalty(
+            &mut self,
+            id: TokenId,
+            receiver: AccountId,
+            fraction: u16,
+        ) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if fraction > FEE_DENOMINATOR {
+                return Err(Error::RoyaltyTooHigh)
+            }
+
+            if !self.exists(id) {
+                return Err(Error::TokenNotFound)
+            }
+
+            self.token_royalties.insert(id, &(receiver, fraction));
+            self.env().emit_event(RoyaltyUpdated {
+                id: Some(id),
+                receiver,
+                fraction,
+            });
+            Ok(())
+        }
+
+        /// Returns the number of live tokens, minted minus burned.
+        /// Alias of `PSP34::total_supply`, kept for one release.
+        #[ink(message)]
+        pub fn total_supply(&self) -> u32 {
+            self.all_tokens_count
+        }
+
+        /// Returns the maximum supply of the token.
+        #[ink(message)]
+        pub fn max_supply(&self) -> u32 {
+            self.max_supply
+        }
+
+        /// Returns the number of tokens ever minted, burned tokens included.
+        #[ink(message)]
+        pub fn total_minted(&self) -> u32 {
+            self.count
+        }
+
+        /// Returns the owner of the token.
+        /// Alias of `PSP34::owner_of`, kept for one release.
+        #[ink(message)]
+        pub fn owner_of(&self, id: TokenId) -> Option<AccountId> {
+            self.token_owners.get(&id)
+        }
+
+        /// Returns the number of tokens owned.
+        /// Alias of `PSP34::balance_of`, kept for one release.
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId) -> u32 {
+            self.balance_of_or_zero(&owner)
+        }
+
+        /// Returns the number of tokens owned by `account` at the end of `block`,
+        /// which must be before the current block.
+        #[ink(message)]
+        pub fn balance_of_at(&self, account: AccountId, block: BlockNumber) -> Result<u32, Error> {
+            checkpoint_at(
+                &self.balance_checkpoints,
+                &self.balance_checkpoint_counts,
+                account,
+                block,
+                self.env().block_number(),
+            )
+        }
+
+        /// Returns the delegate of the votes of `account`, if any.
+        #[ink(message)]
+        pub fn delegates(&self, account: AccountId) -> Option<AccountId> {
+            self.delegates.get(account)
+        }
+
+        /// Delegates the votes of the caller's tokens, current and future, to
+        /// `delegatee`. Tokens of accounts without delegate carry no votes.
+        #[ink(message)]
+        pub fn delegate(&mut self, delegatee: AccountId) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let previous = self.delegates.get(caller);
+            self.delegates.insert(caller, &delegatee);
+            self.env().emit_event(DelegateChanged {
+                delegator: caller,
+                from_delegate: previous,
+                to_delegate: delegatee,
+            });
+            self.move_votes(previous, Some(delegatee), self.balance_of_or_zero(&caller));
+            Ok(())
+        }
+
+        /// Returns the current votes of `account`.
+        #[ink(message)]
+        pub fn get_votes(&self, account: AccountId) -> u32 {
+            latest_checkpoint(&self.vote_checkpoints, &self.vote_checkpoint_counts, account)
+        }
+
+        /// Returns the votes of `account` at the end of `block`, which must be
+        /// before the current block.
+        #[ink(message)]
+        pub fn get_past_votes(&self, account: AccountId, block: BlockNumber) -> Result<u32, Error> {
+            checkpoint_at(
+                &self.vote_checkpoints,
+                &self.vote_checkpoint_counts,
+                account,
+                block,
+                self.env().block_number(),
+            )
+        }
+
+        /// Transfers the token from the caller to the given address.
+        /// Alias of `PSP34::transfer`, kept for one release.
+        #[ink(message)]
+        pub fn transfer(
+            &mut self,
+            destination: AccountId,
+            id: TokenId,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.transfer_token_from(&caller, &destination, id)?;
+            Ok(())
+        }
+
+        /// Transfers token `id` `from` the owner to the `to` address,
+        /// the caller must be the owner or an approved account.
+        #[ink(message)]
+        pub fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            id: TokenId,
+        ) -> Result<(), Error> {
+            self.transfer_token_from(&from, &to, id)?;
+            Ok(())
+        }
+
+        /// Transfers token `id` `from` the owner to the `to` address, if `to` is
+        /// a contract it must accept the token through `Erc721Receiver`.
+        #[ink(message)]
+        pub fn safe_transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            id: TokenId,
+            data: Vec<u8>,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.transfer_token_from(&from, &to, id)?;
+            // An `Err` reverts the transfer
+            self.do_safe_transfer_check(&caller, &from, &to, id, data)?;
+            Ok(())
+        }
+
+        /// Approves the account to transfer the specified token on behalf of the caller.
+        /// Alias of `PSP34::approve`, kept for one release.
+        #[ink(message)]
+        pub fn approve(&mut self, to: AccountId, id: TokenId) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let owner = self.owner_of(id).ok_or(Error::TokenNotFound)?;
+            if !(owner == caller || self.approved_for_all(owner, caller)) {
+                return Err(Error::NotApproved)
+            }
+
+            if to == AccountId::from([0x0; 32]) || to == owner {
+                return Err(Error::NotAllowed)
+            }
+
+            self.token_approvals.insert(id, &to);
+            self.env().emit_event(Approval {
+                from: owner,
+                to,
+                id,
+            });
+            Ok(())
+        }
+
+        /// Approves `spender` for token `id` on behalf of `owner`, who signs the
+        /// approval off-chain instead of paying the fees. `signature` is the ECDSA
+        /// signature of the Blake2x256 hash of the SCALE encoded `(PERMIT_DOMAIN,
+        /// contract, owner, spender, id, nonce, deadline)`, where `nonce` is the
+        /// current nonce of `owner`. The permit expires after `deadline`.
+        #[ink(message)]
+        pub fn permit(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            id: TokenId,
+            deadline: Timestamp,
+            signature: [u8; 65],
+        ) -> Result<(), Error> {
+            if self.env().block_timestamp() > deadline {
+                return Err(Error::PermitExpired)
+            }
+
+            if self.owner_of(id).ok_or(Error::TokenNotFound)? != owner {
+                return Err(Error::NotOwner)
+            }
+
+            if spender == AccountId::from([0x0; 32]) || spender == owner {
+                return Err(Error::NotAllowed)
+            }
+
+            let nonce = self.nonces(owner);
+            let mut message_hash = [0u8; 32];
+            ink_env::hash_encoded::<Blake2x256, _>(
+                &(PERMIT_DOMAIN, self.env().account_id(), owner, spender, id, nonce, deadline),
+                &mut message_hash,
+            );
+            let mut public_key = [0u8; 33];
+            self.env()
+                .ecdsa_recover(&signature, &message_hash, &mut public_key)
+                .map_err(|_| Error::InvalidSignature)?;
+            // ECDSA accounts are the Blake2x256 hash of the compressed public key
+            let mut signer = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&public_key, &mut signer);
+            if AccountId::from(signer) != owner {
+                return Err(Error::InvalidSignature)
+            }
+
+            self.permit_nonces.insert(owner, &(nonce + 1));
+            self.token_approvals.insert(id, &spender);
+            self.env().emit_event(Approval {
+                from: owner,
+                to: spender,
+                id,
+            });
+            Ok(())
+        }
+
+        /// Returns the nonce `owner` must sign for its next permit.
+        #[ink(message)]
+        pub fn nonces(&self, owner: AccountId) -> u64 {
+            self.permit_nonces.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the approved account for the token, if any.
+        #[ink(message)]
+        pub fn get_approved(&self, id: TokenId) -> Option<AccountId> {
+            self.token_approvals.get(id)
+        }
+
+        /// Approves or disapproves the operator for all tokens of the caller.
+        #[ink(message)]
+        pub fn set_approval_for_all(
+            &mut self,
+            to: AccountId,
+            approved: bool,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            if to == caller {
+                return Err(Error::NotAllowed)
+            }
+
+            if approved {
+                self.operator_approvals.insert((&caller, &to), &());
+            } else {
+                self.operator_approvals.remove((&caller, &to));
+            }
+            self.env().emit_event(ApprovalForAll {
+                owner: caller,
+                operator: to,
+                approved,
+            });
+            Ok(())
+        }
+
+        /// Returns `true` if the operator is approved by the owner.
+        #[ink(message)]
+        pub fn is_approved_for_all(&self, owner: AccountId, operator: AccountId) -> bool {
+            self.approved_for_all(owner, operator)
+        }
+
+        /// Creates a new token, only callable by the contract owner unless public
+        /// minting is enabled. Public mints must pay the mint price while the
+        /// sale is active and within the wallet limit.
+        #[ink(message, payable)]
+        pub fn mint(&mut self) -> Result<(), Error> {
+            let caller = self.env().caller();
+            if caller == self.owner {
+                self.mint_token(&caller)?;
+                return Ok(())
+            }
+
+            if !self.public_mint {
+                return Err(Error::MintNotAllowed)
+            }
+
+            if !self.sale_active() {
+                return Err(Error::SaleNotActive)
+            }
+
+            if self.env().transferred_value() != self.mint_price {
+                return Err(Error::InvalidPayment)
+            }
+
+            let minted = self.minted_by(caller);
+            if self.wallet_limit != 0 && minted >= self.wallet_limit {
+                return Err(Error::WalletLimitReached)
+            }
+
+            self.mint_token(&caller)?;
+            self.minted_per_account.insert(caller, &(minted + 1));
+            Ok(())
+        }
+
+        /// Creates a new token for an allowlisted caller during the presale, the
+        /// `proof` must show that `(caller, max_allowed)` is a leaf of the Merkle
+        /// root. Presale mints must pay the mint price.
+        #[ink(message, payable)]
+        pub fn presale_mint(&mut self, proof: Vec<[u8; 32]>, max_allowed: u32) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let root = self.merkle_root.ok_or(Error::PresaleNotActive)?;
+            let mut leaf = [0u8; 32];
+            ink_env::hash_encoded::<Keccak256, _>(&(caller, max_allowed), &mut leaf);
+            if !verify_proof(&proof, root, leaf) {
+                return Err(Error::InvalidProof)
+            }
+
+            if self.env().transferred_value() != self.mint_price {
+                return Err(Error::InvalidPayment)
+            }
+
+            let claimed = self.presale_claimed_by(caller);
+            if claimed >= max_allowed {
+                return Err(Error::WalletLimitReached)
+            }
+
+            self.mint_token(&caller)?;
+            self.presale_claimed.insert(caller, &(claimed + 1));
+            Ok(())
+        }
+
+        /// Returns the Merkle root of the presale allowlist.
+        #[ink(message)]
+        pub fn merkle_root(&self) -> Option<[u8; 32]> {
+            self.merkle_root
+        }
+
+        /// Returns the number of presale mints of an account.
+        #[ink(message)]
+        pub fn presale_claimed_by(&self, account: AccountId) -> u32 {
+            self.presale_claimed.get(account).unwrap_or(0)
+        }
+
+        /// Sets the Merkle root of the presale allowlist, `None` ends the presale.
+        /// Only callable by the contract owner.
+        #[ink(message)]
+        pub fn set_merkle_root(&mut self, merkle_root: Option<[u8; 32]>) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            self.merkle_root = merkle_root;
+            self.env().emit_event(MerkleRootUpdated { merkle_root });
+            Ok(())
+        }
+
+        /// Returns the price of a public mint.
+        #[ink(message)]
+        pub fn mint_price(&self) -> Balance {
+            self.mint_price
+        }
+
+        /// Returns the maximum public mints per account, 0 for no limit.
+        #[ink(message)]
+        pub fn wallet_limit(&self) -> u32 {
+            self.wallet_limit
+        }
+
+        /// Returns the number of public mints of an account.
+        #[ink(message)]
+        pub fn minted_by(&self, account: AccountId) -> u32 {
+            self.minted_per_account.get(account).unwrap_or(0)
+        }
+
+        /// Returns true if the current block is within the public sale.
+        #[ink(message)]
+        pub fn sale_active(&self) -> bool {
+            let now = self.env().block_timestamp();
+            now >= self.sale_start && (self.sale_end == 0 || now < self.sale_end)
+        }
+
+        /// Configures the public sale, only callable by the contract owner.
+        #[ink(message)]
+        pub fn set_sale(
+            &mut self,
+            price: Balance,
+            start: Timestamp,
+            end: Timestamp,
+            wallet_limit: u32,
+        ) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if end != 0 && end <= start {
+                return Err(Error::NotAllowed)
+            }
+
+            self.mint_price = price;
+            self.sale_start = start;
+            self.sale_end = end;
+            self.wallet_limit = wallet_limit;
+            self.env().emit_event(SaleUpdated {
+                price,
+                start,
+                end,
+                wallet_limit,
+            });
+            Ok(())
+        }
+
+        /// Transfers `amount` of the contract balance to `to`, only callable by
+        /// the contract owner.
+        #[ink(message)]
+        pub fn withdraw(&mut self, to: AccountId, amount: Balance) -> Result<(), Error> {
+            self.ensure_contract_owner()?;
+            if amount > self.env().balance() {
+                return Err(Error::InsufficientBalance)
+            }
+
+            self.env().transfer(to, amount).map_err(|_| Error::TransferFailed)?;
+            Ok(())
+        }
+
+        /// Creates a new token for `recipient`, only callable by the contract owner.
+        #[ink(message)]
+        pub fn mint_to(&mut self, recipient: AccountId) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::MintNotAllowed)
+            }
+
+            self.mint_token(&recipient)?;
+            Ok(())
+        }
+
+        /// Creates token `id` for `to`, only callable by the contract owner.
+        /// Ids `1..=max_supply` are reserved for sequential mints, so both never collide.
+        #[ink(message)]
+        pub fn mint_with_id(&mut self, to: AccountId, id: TokenId) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::MintNotAllowed)
+            }
+
+            if id != 0 && id <= self.max_supply as TokenId {
+                return Err(Error::IdReserved)
+            }
+
+            self.mint_token_with_id(&to, id)?;
+            Ok(())
+        }
+
+        /// Destroys a token forever.
+        #[ink(message)]
+        pub fn burn(&mut self, id: TokenId) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.burn_token(&caller, id)?;
+            Ok(())
+        }
+
+        /// Creates a new token for each of the `recipients`, only callable by the
+        /// contract owner. Either all tokens are minted or none.
+        #[ink(message)]
+        pub fn mint_batch(&mut self, recipients: Vec<AccountId>) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::MintNotAllowed)
+            }
+
+            self.ensure_batch_length(recipients.len())?;
+            if recipients.len() as u32 > self.max_supply - self.count {
+                return Err(Error::TokenSupplyLimit)
+            }
+
+            if recipients.contains(&AccountId::from([0x0; 32])) {
+                return Err(Error::NotAllowed)
+            }
+
+            for recipient in recipients.iter() {
+                self.mint_token(recipient)?;
+            }
+            Ok(())
+        }
+
+        /// Transfers each token from the caller to its destination.
+        /// Either all tokens are transferred or none.
+        #[ink(message)]
+        pub fn transfer_batch(&mut self, transfers: Vec<(AccountId, TokenId)>) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let ids: Vec<TokenId> = transfers.iter().map(|(_, id)| *id).collect();
+            self.ensure_batch_owned_by(&caller, &ids)?;
+            if transfers
+                .iter()
+                .any(|(destination, _)| *destination == AccountId::from([0x0; 32]))
+            {
+                return Err(Error::NotAllowed)
+            }
+
+            for (destination, id) in transfers.iter() {
+                self.transfer_token_from(&caller, destination, *id)?;
+            }
+            Ok(())
+        }
+
+        /// Destroys each token of the caller forever.
+        /// Either all tokens are burned or none.
+        #[ink(message)]
+        pub fn burn_batch(&mut self, ids: Vec<TokenId>) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.ensure_batch_owned_by(&caller, &ids)?;
+            for id in ids.iter() {
+                self.burn_token(&caller, *id)?;
+            }
+            Ok(())
+        }
+
+        /// Returns a vector of TokenId's owned by an address, holders of more than
+        /// `MAX_QUERY_LENGTH` tokens must use `token_of_owner_by_index`.
+        #[ink(message)]
+        pub fn tokens_of_owner(&self, owner: AccountId) -> Result<Vec<TokenId>, Error> {
+            let balance = self.balance_of_or_zero(&owner);
+            self.ensure_query_length(balance as usize)?;
+            Ok((0..balance)
+                .filter_map(|index| self.owned_tokens.get((owner, index)))
+                .collect())
+        }
+
+        /// Returns the token at `index` of the owner's list of tokens.
+        #[ink(message)]
+        pub fn token_of_owner_by_index(&self, owner: AccountId, index: u32) -> Option<TokenId> {
+            self.owned_tokens.get((owner, index))
+        }
+
+        /// Returns the token at `index` of all existing tokens.
+        #[ink(message)]
+        pub fn token_by_index(&self, index: u32) -> Option<TokenId> {
+            self.all_tokens.get(index)
+        }
+
+        /// Returns the owner of each token, `None` for tokens that do not exist.
+        #[ink(message)]
+        pub fn owners_of(&self, ids: Vec<TokenId>) -> Result<Vec<Option<AccountId>>, Error> {
+            self.ensure_query_length(ids.len())?;
+            Ok(ids.iter().map(|id| self.token_owners.get(id)).collect())
+        }
+
+        /// Returns the number of tokens owned by each account.
+        #[ink(message)]
+        pub fn balances_of(&self, accounts: Vec<AccountId>) -> Result<Vec<u32>, Error> {
+            self.ensure_query_length(accounts.len())?;
+            Ok(accounts.iter().map(|account| self.balance_of_or_zero(account)).collect())
+        }
+
+        /// Returns at most `limit` existing tokens, starting at index `start` of
+        /// all existing tokens. Burns reorder the list, like `token_by_index`.
+        #[ink(message)]
+        pub fn tokens(&self, start: u32, limit: u32) -> Result<Vec<TokenId>, Error> {
+            self.ensure_query_length(limit as usize)?;
+            let end = start.saturating_add(limit).min(self.all_tokens_count);
+            Ok((start..end).filter_map(|index| self.all_tokens.get(index)).collect())
+        }
+
+        /// Creates the next token for `to`.
+        fn mint_token(&mut self, to: &AccountId) -> Result<TokenId, Error> {
+            let id = self.last_sequential_id + 1;
+            self.mint_token_with_id(to, id)?;
+            self.last_sequential_id = id;
+            Ok(id)
+        }
+
+        /// Creates token `id` for `to`.
+        fn mint_token_with_id(&mut self, to: &AccountId, id: TokenId) -> Result<(), Error> {
+            self.ensure_not_paused()?;
+            if self.count == self.max_supply {
+                return Err(Error::TokenSupplyLimit)
+            }
+
+            if *to == AccountId::from([0x0; 32]) {
+                return Err(Error::NotAllowed)
+            }
+
+            if self.exists(id) {
+                return Err(Error::TokenExists)
+            }
+
+            self.count=self.count+1;
+            
+            self.add_token_to(to, id)?;
+            self.add_token_to_all(id);
+            self.env().emit_event(Transfer {
+                from: Some(AccountId::from([0x0; 32])),
+                to: Some(*to),
+                id,
+            });
+            Ok(())
+        }
+
+        /// Destroys token `id` of `from`.
+        fn burn_token(&mut self, from: &AccountId, id: TokenId) -> Result<(), Error> {
+            self.ensure_not_paused()?;
+            self.ensure_not_locked(id)?;
+            self.remove_token_from(from, id)?;
+            self.remove_token_from_all(id);
+            self.token_uris.remove(id);
+            self.token_royalties.remove(id);
+            self.clear_user(id);
+            self.clear_attributes(id);
+            self.token_locks.remove(id);
+            self.env().emit_event(Transfer { 
+                from: Some(*from),
+                to: Some(AccountId::from([0x0; 32])),
+                id,
+            });
+            Ok(())
+        }
+
+        /// Transfers token `id` `from` the sender to the `to` `AccountId`
+        fn transfer_token_from(
+            &mut self,
+            from: &AccountId,
+            to: &AccountId,
+            id: TokenId,
+        ) -> Result<(), Error> {
+            self.ensure_not_paused()?;
+            if self.soulbound {
+                return Err(Error::NonTransferable)
+            }
+
+            if !self.exists(id) {
+                return Err(Error::TokenNotFound)
+            }
+
+            self.ensure_not_locked(id)?;
+            self.remove_token_from(from, id)?;
+            self.add_token_to(to, id)?;
+            self.clear_user(id);
+            self.token_locks.remove(id);
+
+            self.env().emit_event(Transfer {
+                from: Some(*from),
+                to: Some(*to),
+                id: id,
+            });
+            Ok(())
+        }
+
+        /// Removes token `id` from the owner, the caller must be the owner or approved.
+        fn remove_token_from(
+            &mut self,
+            from: &AccountId,
+            id: TokenId,
+        ) -> Result<(), Error> {
+            let owner = self.token_owners.get(&id);
+            if owner.is_none() {
+                return Err(Error::TokenNotFound)
+            }
+
+            if owner != Some(*from) {
+                return Err(Error::NotOwner)
+            }
+
+            let caller = self.env().caller();
+            if !self.approved_or_owner(caller, id) {
+                return Err(Error::NotApproved)
+            }
+
+            let count = self.balances
+                .get(&from)
+                .map(|c| c - 1)
+                .ok_or(Error::CannotFetchValue)?;
+            self.balances.insert(&from, &count);
+            self.checkpoint_balance(*from, count);
+            self.move_votes(self.delegates.get(from), None, 1);
+            self.token_owners.remove(&id);
+            self.token_approvals.remove(id);
+
+            // Swap the last token of the owner into the removed slot
+            let index = self.owned_tokens_index.get(id).ok_or(Error::CannotFetchValue)?;
+            if index != count {
+                let last = self.owned_tokens.get((from, count)).ok_or(Error::CannotFetchValue)?;
+                self.owned_tokens.insert((from, index), &last);
+                self.owned_tokens_index.insert(last, &index);
+            }
+            self.owned_tokens.remove((from, count));
+            self.owned_tokens_index.remove(id);
+            Ok(())
+        }
+
+        /// Adds the token `id` to the `to` AccountId.
+        fn add_token_to(
+            &mut self,
+            to: &AccountId,
+            id: TokenId,
+        ) -> Result<(), Error> {
+            if self.token_owners.get(&id).is_some() {
+                return Err(Error::TokenExists)
+            }
+
+            if *to == AccountId::from([0x0; 32]) {
+                return Err(Error::NotAllowed)         
+            }
+
+            let count = self.balances
+                .get(to)
+                .map(|c| c + 1)
+                .unwrap_or(1);
+            self.balances.insert(to, &count);
+            self.checkpoint_balance(*to, count);
+            self.move_votes(None, self.delegates.get(to), 1);
+            self.token_owners.insert(&id, to);
+            self.owned_tokens.insert((to, count - 1), &id);
+            self.owned_tokens_index.insert(id, &(count - 1));
+            Ok(())
+        }
+
+        /// Records the balance of `account` at the current block.
+        fn checkpoint_balance(&mut self, account: AccountId, balance: u32) {
+            let block = self.env().block_number();
+            push_checkpoint(
+                &mut self.balance_checkpoints,
+                &mut self.balance_checkpoint_counts,
+                account,
+                block,
+                balance,
+            );
+        }
+
+        /// Moves `amount` votes from the delegate `from` to the delegate `to`,
+        /// `None` stands for tokens without delegate.
+        fn move_votes(&mut self, from: Option<AccountId>, to: Option<AccountId>, amount: u32) {
+            if from == to || amount == 0 {
+                return
+            }
+
+            if let Some(delegate) = from {
+                let previous_votes = self.get_votes(delegate);
+                self.write_votes(delegate, previous_votes, previous_votes - amount);
+            }
+            if let Some(delegate) = to {
+                let previous_votes = self.get_votes(delegate);
+                self.write_votes(delegate, previous_votes, previous_votes + amount);
+            }
+        }
+
+        /// Records the votes of `delegate` at the current block.
+        fn write_votes(&mut self, delegate: AccountId, previous_votes: u32, new_votes: u32) {
+            let block = self.env().block_number();
+            push_checkpoint(
+                &mut self.vote_checkpoints,
+                &mut self.vote_checkpoint_counts,
+                delegate,
+                block,
+                new_votes,
+            );
+            self.env().emit_event(DelegateVotesChanged {
+                delegate,
+                previous_votes,
+                new_votes,
+            });
+        }
+
+        /// Removes the user of token `id`, if any.
+        fn clear_user(&mut self, id: TokenId) {
+            if self.users.get(id).is_some() {
+                self.users.remove(id);
+                self.env().emit_event(UpdateUser {
+                    id,
+                    user: None,
+                    expires: 0,
+                });
+            }
+        }
+
+        /// Removes all attributes of token `id`.
+        fn clear_attributes(&mut self, id: TokenId) {
+            for key in self.attribute_keys.get(id).unwrap_or_default() {
+                self.attributes.remove((id, key));
+            }
+            self.attribute_keys.remove(id);
+        }
+
+        /// Appends token `id` to the global list of tokens.
+        fn add_token_to_all(&mut self, id: TokenId) {
+            self.all_tokens.insert(self.all_tokens_count, &id);
+            self.all_tokens_index.insert(id, &self.all_tokens_count);
+            self.all_tokens_count += 1;
+        }
+
+        /// Removes token `id` from the global list of tokens.
+        fn remove_token_from_all(&mut self, id: TokenId) {
+            let last_index = self.all_tokens_count - 1;
+            if let Some(index) = self.all_tokens_index.get(id) {
+                // Swap the last token into the removed slot
+                if index != last_index {
+                    if let Some(last) = self.all_tokens.get(last_index) {
+                        self.all_tokens.insert(index, &last);
+                        self.all_tokens_index.insert(last, &index);
+                    }
+                }
+                self.all_tokens.remove(last_index);
+                self.all_tokens_index.remove(id);
+                self.all_tokens_count = last_index;
+            }
+        }
+
+        /// Calls `on_erc721_received` on the `to` contract, plain accounts
+        /// always accept the token.
+        fn do_safe_transfer_check(
+            &self,
+            operator: &AccountId,
+            from: &AccountId,
+            to: &AccountId,
+            id: TokenId,
+            data: Vec<u8>,
+        ) -> Result<(), Error> {
+            match self.call_receiver(operator, from, to, id, data) {
+                Ok(Ok(())) => Ok(()),
+                // `to` is not a contract
+                Err(ink_env::Error::NotCallable) => Ok(()),
+                _ => Err(Error::SafeTransferCheckFailed),
+            }
+        }
+
+        /// Calls `Erc721Receiver::on_erc721_received` on the `to` contract.
+        #[cfg(not(test))]
+        fn call_receiver(
+            &self,
+            operator: &AccountId,
+            from: &AccountId,
+            to: &AccountId,
+            id: TokenId,
+            data: Vec<u8>,
+        ) -> Result<Result<(), ReceiverError>, ink_env::Error> {
+            use ink_env::call::{
+                build_call,
+                Call,
+                ExecutionInput,
+                Selector,
+            };
+
+            build_call::<Environment>()
+                .call_type(Call::new().callee(*to).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new([0x15, 0x0B, 0x7A, 0x02]))
+                        .push_arg(operator)
+                        .push_arg(from)
+                        .push_arg(id)
+                        .push_arg(data),
+                )
+                .returns::<Result<(), ReceiverError>>()
+                .fire()
+        }
+
+        /// The off-chain environment cannot call contracts, so tests register
+        /// the answer of receiver contracts instead.
+        #[cfg(test)]
+        fn call_receiver(
+            &self,
+            _operator: &AccountId,
+            _from: &AccountId,
+            to: &AccountId,
+            _id: TokenId,
+            _data: Vec<u8>,
+        ) -> Result<Result<(), ReceiverError>, ink_env::Error> {
+            tests::receiver_answer(to)
+        }
+
+        /// Calls `PSP34Receiver::before_received` on the `to` contract.
+        #[cfg(not(test))]
+        fn call_psp34_receiver(
+            &self,
+            operator: &AccountId,
+            from: &AccountId,
+            to: &AccountId,
+            id: Id,
+            data: Vec<u8>,
+        ) -> Result<Result<(), PSP34ReceiverError>, ink_env::Error> {
+            use ink_env::call::{
+                build_call,
+                Call,
+                ExecutionInput,
+                Selector,
+            };
+
+            build_call::<Environment>()
+                .call_type(Call::new().callee(*to).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new([0xBB, 0x7D, 0xF7, 0x80]))
+                        .push_arg(operator)
+                        .push_arg(from)
+                        .push_arg(id)
+                        .push_arg(data),
+                )
+                .returns::<Result<(), PSP34ReceiverError>>()
+                .fire()
+        }
+
+        #[cfg(test)]
+        fn call_psp34_receiver(
+            &self,
+            _operator: &AccountId,
+            _from: &AccountId,
+            to: &AccountId,
+            _id: Id,
+            _data: Vec<u8>,
+        ) -> Result<Result<(), PSP34ReceiverError>, ink_env::Error> {
+            tests::receiver_answer(to).map(|answer| {
+                answer.map_err(|_| PSP34ReceiverError::TransferRejected(String::from("rejected")))
+            })
+        }
+
+        /// Returns an error if a batch holds more than `MAX_BATCH_LENGTH` items.
+        fn ensure_batch_length(&self, len: usize) -> Result<(), Error> {
+            if len > MAX_BATCH_LENGTH as usize {
+                return Err(Error::BatchTooLarge)
+            }
+            Ok(())
+        }
+
+        /// Returns an error if a query holds more than `MAX_QUERY_LENGTH` items.
+        fn ensure_query_length(&self, len: usize) -> Result<(), Error> {
+            if len > MAX_QUERY_LENGTH as usize {
+                return Err(Error::QueryTooLarge)
+            }
+            Ok(())
+        }
+
+        /// Returns an error unless every token of the batch exists, is owned
+        /// by `owner` and appears only once.
+        fn ensure_batch_owned_by(&self, owner: &AccountId, ids: &[TokenId]) -> Result<(), Error> {
+            self.ensure_batch_length(ids.len())?;
+            for (i, id) in ids.iter().enumerate() {
+                if self.owner_of(*id).ok_or(Error::TokenNotFound)? != *owner {
+                    return Err(Error::NotOwner)
+                }
+
+                if ids[..i].contains(id) {
+                    return Err(Error::NotAllowed)
+                }
+
+                self.ensure_not_locked(*id)?;
+            }
+            Ok(())
+        }
+
+        /// Returns the metadata id of token `id`, shifted by the token offset
+        /// within `1..=max_supply`. Ids outside of that range are not shifted.
+        fn metadata_id(&self, id: TokenId) -> TokenId {
+            let max_supply = self.max_supply as TokenId;
+            if id == 0 || id > max_supply {
+                return id
+            }
+            (id - 1 + self.token_offset as TokenId) % max_supply + 1
+        }
+
+        /// Returns an error if the contract is paused.
+        fn ensure_not_paused(&self) -> Result<(), Error> {
+            if self.paused {
+                return Err(Error::Paused)
+            }
+            Ok(())
+        }
+
+        /// Returns an error if token `id` is locked.
+        fn ensure_not_locked(&self, id: TokenId) -> Result<(), Error> {
+            if self.is_locked(id) {
+                return Err(Error::TokenLocked)
+            }
+            Ok(())
+        }
+
+        /// Returns an error if the caller is not the contract owner.
+        fn ensure_contract_owner(&self) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotContractOwner)
+            }
+            Ok(())
+        }
+
+        /// Returns true if token `id` exists or false if it does not.
+        fn exists(&self, id: TokenId) -> bool {
+            self.token_owners.get(&id).is_some()
+        }
+
+        /// Returns true if `account` is the owner of token `id`, is approved for it,
+        /// or is an operator of the owner.
+        fn approved_or_owner(&self, account: AccountId, id: TokenId) -> bool {
+            match self.owner_of(id) {
+                Some(owner) => {
+                    account == owner
+                        || self.token_approvals.get(id) == Some(account)
+                        || self.approved_for_all(owner, account)
+                }
+                None => false,
+            }
+        }
+
+        /// Returns true if `operator` is approved for all tokens of `owner`.
+        fn approved_for_all(&self, owner: AccountId, operator: AccountId) -> bool {
+            self.operator_approvals.get((&owner, &operator)).is_some()
+        }
+
+        /// Returns the total number of tokens from an account.
+        fn balance_of_or_zero(&self, of: &AccountId) -> u32 {
+            self.balances.get(of).unwrap_or(0)
+        }
+    }
+
+    impl PSP34 for Erc721 {
+        #[ink(message)]
+        fn collection_id(&self) -> Id {
+            Id::Bytes(self.env().account_id().encode())
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> u32 {
+            self.balance_of_or_zero(&owner)
+        }
+
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId> {
+            token_id(&id).and_then(|id| self.token_owners.get(id))
+        }
+
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<Id>) -> bool {
+            self.approved_for_all(owner, operator)
+                || id
+                    .and_then(|id| token_id(&id))
+                    .filter(|id| self.token_owners.get(id) == Some(owner))
+                    .map_or(false, |id| self.token_approvals.get(id) == Some(operator))
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            if operator == caller {
+                return Err(PSP34Error::SelfApprove)
+            }
+
+            let id = match id {
+                Some(id) => token_id(&id).ok_or(PSP34Error::TokenNotExists)?,
+                None => return Ok(self.set_approval_for_all(operator, approved)?),
+            };
+            if approved {
+                return Ok(Erc721::approve(self, operator, id)?)
+            }
+
+            let owner = Erc721::owner_of(self, id).ok_or(PSP34Error::TokenNotExists)?;
+            if !(owner == caller || self.approved_for_all(owner, caller)) {
+                return Err(PSP34Error::NotApproved)
+            }
+
+            if self.token_approvals.get(id) == Some(operator) {
+                self.token_approvals.remove(id);
+                self.env().emit_event(Approval {
+                    from: owner,
+                    to: AccountId::from([0x0; 32]),
+                    id,
+                });
+            }
+            Ok(())
+        }
+
+        /// If `to` is a contract it must accept the token and `data` through
+        /// `PSP34Receiver`.
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, data: Vec<u8>) -> Result<(), PSP34Error> {
+            let token = token_id(&id).ok_or(PSP34Error::TokenNotExists)?;
+            let caller = self.env().caller();
+            self.transfer_token_from(&caller, &to, token)?;
+            // An `Err` reverts the transfer
+            match self.call_psp34_receiver(&caller, &caller, &to, id, data) {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(PSP34ReceiverError::TransferRejected(reason))) => {
+                    Err(PSP34Error::SafeTransferCheckFailed(reason))
+                }
+                // `to` is not a contract
+                Err(ink_env::Error::NotCallable) => Ok(()),
+                Err(_) => {
+                    Err(PSP34Error::SafeTransferCheckFailed(String::from(
+                        "Error during call to receiver",
+                    )))
+                }
+            }
+        }
+
+        #[ink(message)]
+        fn total_supply(&self) -> Balance {
+            self.all_tokens_count as Balance
+        }
+    }
+
+    impl From<Error> for PSP34Error {
+        fn from(error: Error) -> Self {
+            match error {
+                Error::NotOwner | Error::NotApproved => PSP34Error::NotApproved,
+                Error::TokenExists => PSP34Error::TokenExists,
+                Error::TokenNotFound => PSP34Error::TokenNotExists,
+                Error::SafeTransferCheckFailed => {
+                    PSP34Error::SafeTransferCheckFailed(String::from("Erc721Receiver check failed"))
+                }
+                _ => PSP34Error::Custom(format!("{:?}", error)),
+            }
+        }
+    }
+
+    /// Returns the token id of a PSP34 `Id`, `None` for byte ids.
+    fn token_id(id: &Id) -> Option<TokenId> {
+        match *id {
+            Id::U8(id) => Some(id.into()),
+            Id::U16(id) => Some(id.into()),
+            Id::U32(id) => Some(id.into()),
+            Id::U64(id) => Some(id.into()),
+            Id::U128(id) => Some(id),
+            Id::Bytes(_) => None,
+        }
+    }
+
+    /// Records `value` for `account` at `block`, the last checkpoint is
+    /// overwritten if it was recorded at the same block.
+    fn push_checkpoint(
+        checkpoints: &mut Mapping<(AccountId, u32), (BlockNumber, u32)>,
+        counts: &mut Mapping<AccountId, u32>,
+        account: AccountId,
+        block: BlockNumber,
+        value: u32,
+    ) {
+        let count = counts.get(account).unwrap_or(0);
+        if count > 0 && checkpoints.get((account, count - 1)).map(|(b, _)| b) == Some(block) {
+            checkpoints.insert((account, count - 1), &(block, value));
+            return
+        }
+
+        checkpoints.insert((account, count), &(block, value));
+        counts.insert(account, &(count + 1));
+    }
+
+    /// Returns the last value recorded for `account`, 0 if there is none.
+    fn latest_checkpoint(
+        checkpoints: &Mapping<(AccountId, u32), (BlockNumber, u32)>,
+        counts: &Mapping<AccountId, u32>,
+        account: AccountId,
+    ) -> u32 {
+        match counts.get(account).unwrap_or(0) {
+            0 => 0,
+            count => checkpoints.get((account, count - 1)).map(|(_, value)| value).unwrap_or(0),
+        }
+    }
+
+    /// Returns the value of `account` at the end of `block`, found by binary
+    /// search over its checkpoints, 0 before the first one. The value of the
+    /// `current` block can still change, so it and later blocks are rejected.
+    fn checkpoint_at(
+        checkpoints: &Mapping<(AccountId, u32), (BlockNumber, u32)>,
+        counts: &Mapping<AccountId, u32>,
+        account: AccountId,
+        block: BlockNumber,
+        current: BlockNumber,
+    ) -> Result<u32, Error> {
+        if block >= current {
+            return Err(Error::BlockNotFinished)
+        }
+
+        let (mut low, mut high) = (0, counts.get(account).unwrap_or(0));
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match checkpoints.get((account, mid)) {
+                Some((checkpoint_block, _)) if checkpoint_block > block => high = mid,
+                _ => low = mid + 1,
+            }
+        }
+
+        if high == 0 {
+            return Ok(0)
+        }
+        Ok(checkpoints.get((account, high - 1)).map(|(_, value)| value).unwrap_or(0))
+    }
+
+    /// Returns true if `proof` leads from `leaf` to `root`, each pair of nodes
+    /// is sorted before being hashed with Keccak256.
+    fn verify_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+        let computed = proof.iter().fold(leaf, |node, sibling| {
+            let mut parent = [0u8; 32];
+            let pair = if node <= *sibling {
+                [node, *sibling].concat()
+            } else {
+                [*sibling, node].concat()
+            };
+            ink_env::hash_bytes::<Keccak256>(&pair, &mut parent);
+            parent
+        });
+        computed == root
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink_lang as ink;
+        use std::{
+            cell::RefCell,
+            collections::BTreeMap,
+        };
+
+        thread_local! {
+            /// Receiver contracts and whether they accept tokens.
+            static RECEIVERS: RefCell<BTreeMap<AccountId, bool>> = RefCell::new(BTreeMap::new());
+        }
+
+        #[ink::test]
+        fn init_works() {
+            // Create a new contract instance.
+            let erc721 = new_erc721(50, config());
+            // Check the token name
             assert_eq!(erc721.name(), "Test Token".to_string());
             // Check the token symbol
             assert_eq!(erc721.symbol(), "TEST".to_string());
             // Check token supply
-            assert_eq!(erc721.total_supply(), 50);
+            assert_eq!(erc721.max_supply(), 50);
+            assert_eq!(erc721.total_minted(), 0);
+            assert_eq!(erc721.total_supply(), 0);
+            // Check the base URI
+            assert_eq!(erc721.base_uri(), "ipfs://test/".to_string());
+        }
+
+        #[ink::test]
+        fn mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Check existence of TokenId = 1
+            assert_eq!(erc721.owner_of(1), None);
+            // Check Alice's balance
+            assert_eq!(erc721.balance_of(accounts.alice), 0);
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Check if Alice owns TokenId = 1
+            assert_eq!(erc721.balance_of(accounts.alice), 1);
+        }
+
+        #[ink::test]
+        fn mint_limit_works() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Check existence of TokenId = 1, 2, 3
+            assert_eq!(erc721.mint(), Ok(())); 
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Err(Error::TokenSupplyLimit));
+        }
+
+        #[ink::test]
+        fn transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Check that Alice owns a token
+            assert_eq!(erc721.balance_of(accounts.alice), 1);
+            assert_eq!(erc721.owner_of(1), Some(accounts.alice));
+            // Check that Bob does not own a token
+            assert_eq!(erc721.balance_of(accounts.bob), 0);
+            // The first Transfer event (Alice's mint)
+            assert_eq!(1, ink_env::test::recorded_events().count());
+            // Alice transfers token to Bob
+            assert_eq!(erc721.transfer(accounts.bob, 1), Ok(()));
+            // The second Transfer event 
+            assert_eq!(2, ink_env::test::recorded_events().count());
+            // Bob owns token 1
+            assert_eq!(erc721.balance_of(accounts.bob), 1);
+        }
+
+        #[ink::test]
+        fn burn_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Check if Alice owns TokenId = 1
+            assert_eq!(erc721.owner_of(1), Some(accounts.alice));
+            // Burn TokenId = 1
+            assert_eq!(erc721.burn(1), Ok(()));
+            // Check existence of TokenId = 1
+            assert_eq!(erc721.owner_of(1), None);
+        }
+
+        #[ink::test]
+        fn supply_works() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints two tokens
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.total_minted(), 2);
+            assert_eq!(erc721.total_supply(), 2);
+            // Burn TokenId = 1
+            assert_eq!(erc721.burn(1), Ok(()));
+            assert_eq!(erc721.total_minted(), 2);
+            assert_eq!(erc721.total_supply(), 1);
+            // Burned tokens still count towards the maximum supply
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Err(Error::TokenSupplyLimit));
+            assert_eq!(erc721.max_supply(), 3);
+            assert_eq!(erc721.total_minted(), 3);
+            assert_eq!(erc721.total_supply(), 2);
+        }
+
+        #[ink::test]
+        fn burn_fails_token_not_found() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Check if burn fails. 
+            assert_eq!(erc721.burn(1), Err(Error::TokenNotFound));
+        }
+
+        #[ink::test]
+        fn burn_fails_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Try burning this token with a different account
+            set_caller(accounts.eve);
+            assert_eq!(erc721.burn(1), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn approved_transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Alice approves Bob to transfer TokenId = 1
+            assert_eq!(erc721.approve(accounts.bob, 1), Ok(()));
+            assert_eq!(erc721.get_approved(1), Some(accounts.bob));
+            // Bob transfers TokenId = 1 from Alice to Eve
+            set_caller(accounts.bob);
+            assert_eq!(erc721.transfer_from(accounts.alice, accounts.eve, 1), Ok(()));
+            // Eve owns TokenId = 1
+            assert_eq!(erc721.owner_of(1), Some(accounts.eve));
+            assert_eq!(erc721.balance_of(accounts.alice), 0);
+            assert_eq!(erc721.balance_of(accounts.eve), 1);
+            // The approval is cleared on transfer
+            assert_eq!(erc721.get_approved(1), None);
+            // Mint, Approval and Transfer events
+            assert_eq!(3, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn approved_for_all_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints two tokens
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            // Alice sets Bob as an operator
+            assert_eq!(erc721.set_approval_for_all(accounts.bob, true), Ok(()));
+            assert!(erc721.is_approved_for_all(accounts.alice, accounts.bob));
+            // Bob transfers both tokens from Alice to Eve
+            set_caller(accounts.bob);
+            assert_eq!(erc721.transfer_from(accounts.alice, accounts.eve, 1), Ok(()));
+            assert_eq!(erc721.transfer_from(accounts.alice, accounts.eve, 2), Ok(()));
+            assert_eq!(erc721.balance_of(accounts.eve), 2);
+            // Alice removes Bob as an operator
+            set_caller(accounts.alice);
+            assert_eq!(erc721.set_approval_for_all(accounts.bob, false), Ok(()));
+            assert!(!erc721.is_approved_for_all(accounts.alice, accounts.bob));
+        }
+
+        #[ink::test]
+        fn operator_can_approve() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints a token and sets Bob as an operator
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.set_approval_for_all(accounts.bob, true), Ok(()));
+            // Bob approves Charlie for TokenId = 1
+            set_caller(accounts.bob);
+            assert_eq!(erc721.approve(accounts.charlie, 1), Ok(()));
+            assert_eq!(erc721.get_approved(1), Some(accounts.charlie));
+        }
+
+        #[ink::test]
+        fn transfer_from_fails_not_approved() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Eve cannot transfer or approve Alice's token
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc721.transfer_from(accounts.alice, accounts.eve, 1),
+                Err(Error::NotApproved)
+            );
+            assert_eq!(erc721.approve(accounts.eve, 1), Err(Error::NotApproved));
+            // `from` must be the owner of the token
+            assert_eq!(
+                erc721.transfer_from(accounts.bob, accounts.eve, 1),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc721.owner_of(1), Some(accounts.alice));
+        }
+
+        #[ink::test]
+        fn tokens_of_owner_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints three tokens
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.tokens_of_owner(accounts.alice), Ok(vec![1, 2, 3]));
+            // Alice transfers TokenId = 1 to Bob, the last token fills the gap
+            assert_eq!(erc721.transfer(accounts.bob, 1), Ok(()));
+            assert_eq!(erc721.tokens_of_owner(accounts.alice), Ok(vec![3, 2]));
+            assert_eq!(erc721.tokens_of_owner(accounts.bob), Ok(vec![1]));
+            assert_eq!(erc721.token_of_owner_by_index(accounts.alice, 0), Some(3));
+            assert_eq!(erc721.token_of_owner_by_index(accounts.alice, 2), None);
+            assert_eq!(erc721.tokens_of_owner(accounts.eve), Ok(vec![]));
+        }
+
+        #[ink::test]
+        fn token_by_index_works() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints three tokens
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.token_by_index(0), Some(1));
+            assert_eq!(erc721.token_by_index(2), Some(3));
+            // Burn TokenId = 1, the last token fills the gap
+            assert_eq!(erc721.burn(1), Ok(()));
+            assert_eq!(erc721.token_by_index(0), Some(3));
+            assert_eq!(erc721.token_by_index(1), Some(2));
+            assert_eq!(erc721.token_by_index(2), None);
+        }
+
+        #[ink::test]
+        fn safe_transfer_from_fails_not_approved() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            // TokenId = 1 does not exist yet
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.alice, accounts.bob, 1, vec![]),
+                Err(Error::TokenNotFound)
+            );
+            // Alice mints a token
+            assert_eq!(erc721.mint(), Ok(()));
+            // Eve cannot safely transfer Alice's token
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.alice, accounts.eve, 1, vec![]),
+                Err(Error::NotApproved)
+            );
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.bob, accounts.eve, 1, vec![]),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc721.owner_of(1), Some(accounts.alice));
+        }
+
+        #[ink::test]
+        fn safe_transfer_from_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            // Bob is a plain account, Charlie a contract that accepts tokens
+            set_receiver(accounts.charlie, true);
+            assert_eq!(erc721.safe_transfer_from(accounts.alice, accounts.bob, 1, vec![]), Ok(()));
+            assert_eq!(erc721.safe_transfer_from(accounts.alice, accounts.charlie, 2, vec![1]), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.bob));
+            assert_eq!(erc721.owner_of(2), Some(accounts.charlie));
+        }
+
+        #[ink::test]
+        fn safe_transfer_from_rejected_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, config());
+            assert_eq!(erc721.mint(), Ok(()));
+            // Charlie is a contract that rejects tokens, the transfer is reverted on-chain
+            set_receiver(accounts.charlie, false);
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.alice, accounts.charlie, 1, vec![]),
+                Err(Error::SafeTransferCheckFailed)
+            );
+        }
+
+        #[ink::test]
+        fn token_uri_works() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // TokenId = 1 does not exist yet
+            assert_eq!(erc721.token_uri(1), None);
+            // Alice mints two tokens
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.token_uri(1), Some("ipfs://test/1".to_string()));
+            // Override the URI of TokenId = 2
+            assert_eq!(erc721.set_token_uri(2, "ipfs://other".to_string()), Ok(()));
+            assert_eq!(erc721.token_uri(2), Some("ipfs://other".to_string()));
+            assert_eq!(erc721.set_token_uri(3, "ipfs://other".to_string()), Err(Error::TokenNotFound));
+            // Update the base URI
+            assert_eq!(erc721.set_base_uri("ipfs://new/".to_string()), Ok(()));
+            assert_eq!(erc721.token_uri(1), Some("ipfs://new/1".to_string()));
+            assert_eq!(erc721.token_uri(2), Some("ipfs://other".to_string()));
+            // Two Transfer events and one BaseUriUpdated event
+            assert_eq!(3, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn set_base_uri_fails_not_contract_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.owner(), accounts.alice);
+            // Eve cannot update the URIs
+            set_caller(accounts.eve);
+            assert_eq!(erc721.set_base_uri("ipfs://eve/".to_string()), Err(Error::NotContractOwner));
+            assert_eq!(erc721.set_token_uri(1, "ipfs://eve".to_string()), Err(Error::NotContractOwner));
+            assert_eq!(erc721.base_uri(), "ipfs://test/".to_string());
+        }
+
+        #[ink::test]
+        fn mint_to_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance without public minting.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            // Alice (the contract owner) mints a token to Bob
+            assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.bob));
+            // Alice can still mint to herself
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.owner_of(2), Some(accounts.alice));
+            // Cannot mint to the zero address
+            assert_eq!(erc721.mint_to(AccountId::from([0x0; 32])), Err(Error::NotAllowed));
+        }
+
+        #[ink::test]
+        fn mint_fails_not_allowed() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance without public minting.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            assert!(!erc721.public_mint());
+            // Eve can neither mint nor mint_to
+            set_caller(accounts.eve);
+            assert_eq!(erc721.mint(), Err(Error::MintNotAllowed));
+            assert_eq!(erc721.mint_to(accounts.eve), Err(Error::MintNotAllowed));
+            assert_eq!(erc721.balance_of(accounts.eve), 0);
+        }
+
+        #[ink::test]
+        fn public_mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance with public minting.
+            let mut erc721 = new_erc721(50, config());
+            // Eve can mint to herself but not to others
+            set_caller(accounts.eve);
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.eve));
+            assert_eq!(erc721.mint_to(accounts.bob), Err(Error::MintNotAllowed));
+        }
+
+        #[ink::test]
+        fn transfer_ownership_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance without public minting.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            // Eve cannot take the ownership
+            set_caller(accounts.eve);
+            assert_eq!(erc721.transfer_ownership(accounts.eve), Err(Error::NotContractOwner));
+            // Alice transfers the ownership to Bob
+            set_caller(accounts.alice);
+            assert_eq!(erc721.transfer_ownership(accounts.bob), Ok(()));
+            assert_eq!(erc721.owner(), accounts.bob);
+            assert_eq!(erc721.mint(), Err(Error::MintNotAllowed));
+            // Bob mints and renounces the ownership
+            set_caller(accounts.bob);
+            assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            assert_eq!(erc721.renounce_ownership(), Ok(()));
+            assert_eq!(erc721.owner(), AccountId::from([0x0; 32]));
+            assert_eq!(erc721.mint_to(accounts.bob), Err(Error::MintNotAllowed));
+            // Two OwnershipTransferred events and one Transfer event
+            assert_eq!(3, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn mint_batch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(3, Config { public_mint: false, ..config() });
+            // Alice airdrops a token to Bob and Charlie
+            assert_eq!(erc721.mint_batch(vec![accounts.bob, accounts.charlie]), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.bob));
+            assert_eq!(erc721.owner_of(2), Some(accounts.charlie));
+            // One Transfer event per token
+            assert_eq!(2, ink_env::test::recorded_events().count());
+            // Only one token left, nothing is minted
+            assert_eq!(
+                erc721.mint_batch(vec![accounts.django, accounts.eve]),
+                Err(Error::TokenSupplyLimit)
+            );
+            assert_eq!(erc721.total_minted(), 2);
+            // Eve cannot mint
+            set_caller(accounts.eve);
+            assert_eq!(erc721.mint_batch(vec![accounts.eve]), Err(Error::MintNotAllowed));
+        }
+
+        #[ink::test]
+        fn mint_batch_fails_too_large() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(100, Config { public_mint: false, ..config() });
+            let recipients = vec![accounts.bob; MAX_BATCH_LENGTH as usize + 1];
+            assert_eq!(erc721.mint_batch(recipients), Err(Error::BatchTooLarge));
+            assert_eq!(erc721.total_minted(), 0);
         }
 
         #[ink::test]
-        fn mint_works() {
+        fn transfer_batch_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             // Create a new contract instance.
-            let mut erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 50);
-            // Check existence of TokenId = 1
-            assert_eq!(erc721.owner_of(1), None);
-            // Check Alice's balance
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints three tokens
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 3]), Ok(()));
+            // Alice transfers two tokens to Bob and one to Charlie
+            assert_eq!(
+                erc721.transfer_batch(vec![(accounts.bob, 1), (accounts.bob, 2), (accounts.charlie, 3)]),
+                Ok(())
+            );
             assert_eq!(erc721.balance_of(accounts.alice), 0);
-            // Alice mints a token
+            assert_eq!(erc721.balance_of(accounts.bob), 2);
+            assert_eq!(erc721.owner_of(3), Some(accounts.charlie));
+            // Three mint and three transfer events
+            assert_eq!(6, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn transfer_batch_is_all_or_nothing() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints two tokens and Bob one
+            assert_eq!(erc721.mint_batch(vec![accounts.alice, accounts.alice, accounts.bob]), Ok(()));
+            // TokenId = 3 belongs to Bob, nothing is transferred
+            assert_eq!(
+                erc721.transfer_batch(vec![(accounts.charlie, 1), (accounts.charlie, 3)]),
+                Err(Error::NotOwner)
+            );
+            // TokenId = 2 appears twice, nothing is transferred
+            assert_eq!(
+                erc721.transfer_batch(vec![(accounts.charlie, 2), (accounts.eve, 2)]),
+                Err(Error::NotAllowed)
+            );
+            assert_eq!(erc721.balance_of(accounts.alice), 2);
+            assert_eq!(erc721.balance_of(accounts.charlie), 0);
+        }
+
+        #[ink::test]
+        fn burn_batch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints three tokens
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 3]), Ok(()));
+            // TokenId = 4 does not exist, nothing is burned
+            assert_eq!(erc721.burn_batch(vec![1, 4]), Err(Error::TokenNotFound));
+            assert_eq!(erc721.total_supply(), 3);
+            // Alice burns two tokens
+            assert_eq!(erc721.burn_batch(vec![1, 3]), Ok(()));
+            assert_eq!(erc721.total_supply(), 1);
+            assert_eq!(erc721.tokens_of_owner(accounts.alice), Ok(vec![2]));
+        }
+
+        #[ink::test]
+        fn royalty_info_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance with a 5% royalty to Alice.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints two tokens
             assert_eq!(erc721.mint(), Ok(()));
-            // Check if Alice owns TokenId = 1
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.royalty_info(1, 1_000), (accounts.alice, 50));
+            // TokenId = 2 pays 10% to Bob
+            assert_eq!(erc721.set_token_royalty(2, accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc721.royalty_info(2, 1_000), (accounts.bob, 100));
+            assert_eq!(erc721.royalty_info(1, 1_000), (accounts.alice, 50));
+            // The default royalty is now 2.5% to Charlie
+            assert_eq!(erc721.set_default_royalty(accounts.charlie, 250), Ok(()));
+            assert_eq!(erc721.royalty_info(1, 1_000), (accounts.charlie, 25));
+            assert_eq!(erc721.royalty_info(2, 1_000), (accounts.bob, 100));
+            // Large sale prices do not overflow
+            assert_eq!(erc721.royalty_info(2, Balance::MAX), (accounts.bob, Balance::MAX / 10));
+            // Two Transfer events and two RoyaltyUpdated events
+            assert_eq!(4, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn set_royalty_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint(), Ok(()));
+            // Fractions above 100% are rejected
+            assert_eq!(erc721.set_default_royalty(accounts.bob, 10_001), Err(Error::RoyaltyTooHigh));
+            assert_eq!(erc721.set_token_royalty(1, accounts.bob, 10_001), Err(Error::RoyaltyTooHigh));
+            assert_eq!(erc721.set_token_royalty(2, accounts.bob, 100), Err(Error::TokenNotFound));
+            // Eve cannot update royalties
+            set_caller(accounts.eve);
+            assert_eq!(erc721.set_default_royalty(accounts.eve, 100), Err(Error::NotContractOwner));
+            assert_eq!(erc721.set_token_royalty(1, accounts.eve, 100), Err(Error::NotContractOwner));
+            assert_eq!(erc721.royalty_info(1, 1_000), (accounts.alice, 50));
+        }
+
+        #[ink::test]
+        fn pause_works() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert!(!erc721.paused());
+            assert_eq!(erc721.unpause(), Err(Error::NotPaused));
+            // Alice pauses the contract
+            assert_eq!(erc721.pause(), Ok(()));
+            assert!(erc721.paused());
+            assert_eq!(erc721.pause(), Err(Error::Paused));
+            assert_eq!(erc721.mint(), Err(Error::Paused));
+            // Alice unpauses the contract
+            assert_eq!(erc721.unpause(), Ok(()));
+            assert!(!erc721.paused());
+            assert_eq!(erc721.mint(), Ok(()));
+            // Paused, Unpaused and Transfer events
+            assert_eq!(3, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn pause_fails_not_contract_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Eve cannot pause the contract
+            set_caller(accounts.eve);
+            assert_eq!(erc721.pause(), Err(Error::NotContractOwner));
+            // Eve cannot unpause the contract
+            set_caller(accounts.alice);
+            assert_eq!(erc721.pause(), Ok(()));
+            set_caller(accounts.eve);
+            assert_eq!(erc721.unpause(), Err(Error::NotContractOwner));
+            assert!(erc721.paused());
+        }
+
+        #[ink::test]
+        fn paused_mint_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.pause(), Ok(()));
+            assert_eq!(erc721.mint(), Err(Error::Paused));
+            assert_eq!(erc721.mint_to(accounts.bob), Err(Error::Paused));
+            assert_eq!(erc721.mint_batch(vec![accounts.bob, accounts.charlie]), Err(Error::Paused));
+            assert_eq!(erc721.total_minted(), 0);
+        }
+
+        #[ink::test]
+        fn paused_transfer_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints two tokens and approves Bob for TokenId = 1
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            assert_eq!(erc721.approve(accounts.bob, 1), Ok(()));
+            assert_eq!(erc721.pause(), Ok(()));
+            assert_eq!(erc721.transfer(accounts.bob, 1), Err(Error::Paused));
+            assert_eq!(erc721.transfer_batch(vec![(accounts.bob, 1), (accounts.bob, 2)]), Err(Error::Paused));
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.alice, accounts.bob, 1, vec![]),
+                Err(Error::Paused)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc721.transfer_from(accounts.alice, accounts.bob, 1), Err(Error::Paused));
+            assert_eq!(erc721.balance_of(accounts.alice), 2);
+        }
+
+        #[ink::test]
+        fn paused_burn_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints two tokens
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            assert_eq!(erc721.pause(), Ok(()));
+            assert_eq!(erc721.burn(1), Err(Error::Paused));
+            assert_eq!(erc721.burn_batch(vec![1, 2]), Err(Error::Paused));
+            assert_eq!(erc721.total_supply(), 2);
+        }
+
+        #[ink::test]
+        fn migrate_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.storage_version(), STORAGE_VERSION);
+            assert_eq!(erc721.migrate(), Err(Error::AlreadyMigrated));
+            // Alice mints two tokens and transfers one to Bob
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            assert_eq!(erc721.transfer(accounts.bob, 2), Ok(()));
+            // Simulate storage written by the previous version. The off-chain
+            // environment cannot run `set_code_hash`, so the code swap itself
+            // is not covered here.
+            erc721.storage_version = STORAGE_VERSION - 1;
+            // Eve cannot migrate
+            set_caller(accounts.eve);
+            assert_eq!(erc721.migrate(), Err(Error::NotContractOwner));
+            // Alice migrates once
+            set_caller(accounts.alice);
+            assert_eq!(erc721.migrate(), Ok(()));
+            assert_eq!(erc721.storage_version(), STORAGE_VERSION);
+            assert_eq!(erc721.migrate(), Err(Error::AlreadyMigrated));
+            // Owners and balances survive the version bump
+            assert_eq!(erc721.owner_of(1), Some(accounts.alice));
+            assert_eq!(erc721.owner_of(2), Some(accounts.bob));
             assert_eq!(erc721.balance_of(accounts.alice), 1);
+            assert_eq!(erc721.balance_of(accounts.bob), 1);
         }
 
         #[ink::test]
-        fn mint_limit_works() {
+        fn upgrade_fails_not_contract_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             // Create a new contract instance.
-            let mut erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 3);
-            // Check existence of TokenId = 1, 2, 3
-            assert_eq!(erc721.mint(), Ok(())); 
+            let mut erc721 = new_erc721(50, config());
+            // Eve cannot upgrade the contract
+            set_caller(accounts.eve);
+            assert_eq!(erc721.upgrade([0x1; 32]), Err(Error::NotContractOwner));
+        }
+
+        #[ink::test]
+        fn reveal_works() {
+            let seed = b"secret seed".to_vec();
+            let mut provenance = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&seed, &mut provenance);
+            // Create a new contract instance with hidden metadata.
+            let mut erc721 = Erc721::new(
+                "Test Token".to_string(),
+                "TEST".to_string(),
+                50,
+                "ipfs://hidden".to_string(),
+                Config { provenance_hash: Some(provenance), ..config() },
+            );
+            assert!(!erc721.revealed());
+            assert_eq!(erc721.provenance_hash(), Some(provenance));
+            // Alice mints two tokens, both show the placeholder
+            assert_eq!(erc721.mint_batch(vec![AccountId::from([0x1; 32]); 2]), Ok(()));
+            assert_eq!(erc721.token_uri(1), Some("ipfs://hidden".to_string()));
+            assert_eq!(erc721.token_uri(2), Some("ipfs://hidden".to_string()));
+            // Alice reveals the metadata
+            assert_eq!(erc721.reveal("ipfs://test/".to_string(), seed.clone()), Ok(()));
+            assert!(erc721.revealed());
+            let uri = erc721.token_uri(1).unwrap();
+            assert_ne!(uri, "ipfs://hidden".to_string());
+            assert!(uri.starts_with("ipfs://test/"));
+            assert_ne!(erc721.token_uri(2).unwrap(), uri);
+            // Metadata ids are shifted by the token offset
+            let offset = erc721.token_offset;
+            assert_eq!(uri, format!("ipfs://test/{}", offset % 50 + 1));
+            // The metadata can only be revealed once
+            assert_eq!(erc721.reveal("ipfs://other/".to_string(), seed), Err(Error::AlreadyRevealed));
+        }
+
+        #[ink::test]
+        fn set_uri_after_reveal_fails() {
+            let seed = b"secret seed".to_vec();
+            let mut provenance = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&seed, &mut provenance);
+            // Create a new contract instance with hidden metadata.
+            let mut erc721 = Erc721::new(
+                "Test Token".to_string(),
+                "TEST".to_string(),
+                50,
+                "ipfs://hidden".to_string(),
+                Config { provenance_hash: Some(provenance), ..config() },
+            );
             assert_eq!(erc721.mint(), Ok(()));
+            // The placeholder can change until the reveal, per-token URIs never
+            assert_eq!(erc721.set_base_uri("ipfs://hidden2".to_string()), Ok(()));
+            assert_eq!(erc721.set_token_uri(1, "ipfs://other".to_string()), Err(Error::MetadataFrozen));
+            assert_eq!(erc721.reveal("ipfs://test/".to_string(), seed), Ok(()));
+            let uri = erc721.token_uri(1);
+            // The revealed metadata cannot be moved
+            assert_eq!(erc721.set_base_uri("ipfs://other/".to_string()), Err(Error::MetadataFrozen));
+            assert_eq!(erc721.set_token_uri(1, "ipfs://other".to_string()), Err(Error::MetadataFrozen));
+            assert_eq!(erc721.base_uri(), "ipfs://test/".to_string());
+            assert_eq!(erc721.token_uri(1), uri);
+        }
+
+        #[ink::test]
+        fn reveal_fails_invalid_seed() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut provenance = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(b"secret seed", &mut provenance);
+            // Create a new contract instance with hidden metadata.
+            let mut erc721 = Erc721::new(
+                "Test Token".to_string(),
+                "TEST".to_string(),
+                50,
+                "ipfs://hidden".to_string(),
+                Config { provenance_hash: Some(provenance), ..config() },
+            );
             assert_eq!(erc721.mint(), Ok(()));
-            assert_eq!(erc721.mint(), Err(Error::TokenSupplyLimit));
+            // A wrong preimage is rejected
+            assert_eq!(
+                erc721.reveal("ipfs://test/".to_string(), b"wrong seed".to_vec()),
+                Err(Error::InvalidSeed)
+            );
+            assert!(!erc721.revealed());
+            assert_eq!(erc721.token_uri(1), Some("ipfs://hidden".to_string()));
+            // Eve cannot reveal
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc721.reveal("ipfs://test/".to_string(), b"secret seed".to_vec()),
+                Err(Error::NotContractOwner)
+            );
         }
 
         #[ink::test]
-        fn transfer_works() {
+        fn paid_mint_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             // Create a new contract instance.
-            let mut erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 3);
-            // Alice mints a token
+            let mut erc721 = new_erc721(50, config());
+            // The sale starts at the next block and allows two mints per account
+            let start = ink_env::block_timestamp::<ink_env::DefaultEnvironment>() + 1;
+            assert_eq!(erc721.set_sale(100, start, 0, 2), Ok(()));
+            set_caller(accounts.eve);
+            set_value_transferred(100);
+            assert_eq!(erc721.mint(), Err(Error::SaleNotActive));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert!(erc721.sale_active());
+            // The exact price must be paid
+            set_value_transferred(99);
+            assert_eq!(erc721.mint(), Err(Error::InvalidPayment));
+            set_value_transferred(100);
             assert_eq!(erc721.mint(), Ok(()));
-            // Check that Alice owns a token
-            assert_eq!(erc721.balance_of(accounts.alice), 1);
-            assert_eq!(erc721.owner_of(1), Some(accounts.alice));
-            // Check that Bob does not own a token
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.minted_by(accounts.eve), 2);
+            // Eve reached the wallet limit
+            assert_eq!(erc721.mint(), Err(Error::WalletLimitReached));
+            assert_eq!(erc721.balance_of(accounts.eve), 2);
+            // The contract owner mints for free
+            set_caller(accounts.alice);
+            set_value_transferred(0);
+            assert_eq!(erc721.mint(), Ok(()));
+        }
+
+        #[ink::test]
+        fn sale_ends() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            // The sale ends at the next block
+            let end = ink_env::block_timestamp::<ink_env::DefaultEnvironment>() + 1;
+            assert_eq!(erc721.set_sale(0, 0, end, 0), Ok(()));
+            assert_eq!(erc721.set_sale(0, end, end, 0), Err(Error::NotAllowed));
+            set_caller(accounts.eve);
+            assert_eq!(erc721.mint(), Ok(()));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert!(!erc721.sale_active());
+            assert_eq!(erc721.mint(), Err(Error::SaleNotActive));
+            // Eve cannot configure the sale
+            assert_eq!(erc721.set_sale(0, 0, 0, 0), Err(Error::NotContractOwner));
+        }
+
+        #[ink::test]
+        fn withdraw_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 1_000);
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(accounts.bob, 0);
+            // Eve cannot withdraw
+            set_caller(accounts.eve);
+            assert_eq!(erc721.withdraw(accounts.eve, 100), Err(Error::NotContractOwner));
+            // Alice withdraws to Bob
+            set_caller(accounts.alice);
+            assert_eq!(erc721.withdraw(accounts.bob, 1_001), Err(Error::InsufficientBalance));
+            assert_eq!(erc721.withdraw(accounts.bob, 400), Ok(()));
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob),
+                Ok(400)
+            );
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(contract),
+                Ok(600)
+            );
+        }
+
+        #[ink::test]
+        fn presale_mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance without public minting.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            let leaves = vec![
+                leaf(accounts.bob, 2),
+                leaf(accounts.charlie, 1),
+                leaf(accounts.django, 1),
+            ];
+            let (root, bob_proof) = merkle_root_and_proof(&leaves, 0);
+            let (_, django_proof) = merkle_root_and_proof(&leaves, 2);
+            // The presale has not started
+            set_caller(accounts.bob);
+            assert_eq!(erc721.presale_mint(bob_proof.clone(), 2), Err(Error::PresaleNotActive));
+            set_caller(accounts.alice);
+            assert_eq!(erc721.set_merkle_root(Some(root)), Ok(()));
+            // Bob mints his two allowlisted tokens
+            set_caller(accounts.bob);
+            assert_eq!(erc721.presale_mint(bob_proof.clone(), 2), Ok(()));
+            assert_eq!(erc721.presale_mint(bob_proof.clone(), 2), Ok(()));
+            assert_eq!(erc721.presale_mint(bob_proof, 2), Err(Error::WalletLimitReached));
+            assert_eq!(erc721.presale_claimed_by(accounts.bob), 2);
+            // Django's proof works with an odd number of leaves
+            set_caller(accounts.django);
+            assert_eq!(erc721.presale_mint(django_proof.clone(), 1), Ok(()));
+            assert_eq!(erc721.balance_of(accounts.django), 1);
+            // The public mint stays closed
+            assert_eq!(erc721.mint(), Err(Error::MintNotAllowed));
+            // Alice ends the presale
+            set_caller(accounts.alice);
+            assert_eq!(erc721.set_merkle_root(None), Ok(()));
+            set_caller(accounts.django);
+            assert_eq!(erc721.presale_mint(django_proof, 1), Err(Error::PresaleNotActive));
+        }
+
+        #[ink::test]
+        fn presale_mint_fails_invalid_proof() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance without public minting.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            let leaves = vec![leaf(accounts.bob, 1), leaf(accounts.charlie, 1)];
+            let (root, bob_proof) = merkle_root_and_proof(&leaves, 0);
+            assert_eq!(erc721.set_merkle_root(Some(root)), Ok(()));
+            // Bob cannot raise his allowance
+            set_caller(accounts.bob);
+            assert_eq!(erc721.presale_mint(bob_proof.clone(), 5), Err(Error::InvalidProof));
+            // A tampered proof is rejected
+            let mut tampered = bob_proof.clone();
+            tampered[0][0] ^= 0x1;
+            assert_eq!(erc721.presale_mint(tampered, 1), Err(Error::InvalidProof));
+            // Eve cannot use Bob's proof
+            set_caller(accounts.eve);
+            assert_eq!(erc721.presale_mint(bob_proof, 1), Err(Error::InvalidProof));
+            assert_eq!(erc721.total_minted(), 0);
+            // Eve cannot set the Merkle root
+            assert_eq!(erc721.set_merkle_root(None), Err(Error::NotContractOwner));
+        }
+
+        #[ink::test]
+        fn soulbound_transfer_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance with soulbound tokens.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, soulbound: true, ..config() });
+            assert_eq!(erc721.locked(1), None);
+            // Alice issues a token to Bob
+            assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            assert_eq!(erc721.locked(1), Some(true));
+            // It is soulbound, not locked
+            assert!(!erc721.is_locked(1));
+            // Bob cannot transfer it, even through an approval
+            set_caller(accounts.bob);
+            assert_eq!(erc721.transfer(accounts.eve, 1), Err(Error::NonTransferable));
+            assert_eq!(erc721.transfer_batch(vec![(accounts.eve, 1)]), Err(Error::NonTransferable));
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.bob, accounts.eve, 1, vec![]),
+                Err(Error::NonTransferable)
+            );
+            assert_eq!(erc721.approve(accounts.eve, 1), Ok(()));
+            set_caller(accounts.eve);
+            assert_eq!(erc721.transfer_from(accounts.bob, accounts.eve, 1), Err(Error::NonTransferable));
+            assert_eq!(erc721.owner_of(1), Some(accounts.bob));
+            // Bob can still burn it
+            set_caller(accounts.bob);
+            assert_eq!(erc721.burn(1), Ok(()));
             assert_eq!(erc721.balance_of(accounts.bob), 0);
-            // The first Transfer event (Alice's mint)
-            assert_eq!(1, ink_env::test::recorded_events().count());
-            // Alice transfers token to Bob
-            assert_eq!(erc721.transfer(accounts.bob, 1), Ok(()));
-            // The second Transfer event 
-            assert_eq!(2, ink_env::test::recorded_events().count());
-            // Bob owns token 1
-            assert_eq!(erc721.balance_of(accounts.bob), 1);
         }
 
         #[ink::test]
-        fn burn_works() {
+        fn transferable_tokens_are_not_locked() {
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.locked(1), Some(false));
+        }
+
+        #[ink::test]
+        fn set_user_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             // Create a new contract instance.
-            let mut erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 50);
-            // Alice mints a token
+            let mut erc721 = new_erc721(50, config());
             assert_eq!(erc721.mint(), Ok(()));
-            // Check if Alice owns TokenId = 1
+            // Alice lends TokenId = 1 to Bob until the next block
+            let expires = ink_env::block_timestamp::<ink_env::DefaultEnvironment>() + 1;
+            assert_eq!(erc721.set_user(1, accounts.bob, expires), Ok(()));
+            assert_eq!(erc721.user_of(1), Some(accounts.bob));
+            assert_eq!(erc721.user_expires(1), expires);
+            // Alice still owns the token
             assert_eq!(erc721.owner_of(1), Some(accounts.alice));
-            // Burn TokenId = 1
+            // The user role expires
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.user_of(1), None);
+            assert_eq!(erc721.user_expires(1), expires);
+            // Mint, UpdateUser events
+            assert_eq!(2, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn user_cleared_on_transfer() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint(), Ok(()));
+            // Alice approves Charlie, who lends TokenId = 1 to Bob
+            assert_eq!(erc721.approve(accounts.charlie, 1), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc721.set_user(1, accounts.bob, u64::MAX), Ok(()));
+            assert_eq!(erc721.user_of(1), Some(accounts.bob));
+            // Eve cannot set the user
+            set_caller(accounts.eve);
+            assert_eq!(erc721.set_user(1, accounts.eve, u64::MAX), Err(Error::NotApproved));
+            assert_eq!(erc721.set_user(2, accounts.eve, u64::MAX), Err(Error::TokenNotFound));
+            // The user is cleared when Alice transfers the token
+            set_caller(accounts.alice);
+            assert_eq!(erc721.transfer(accounts.django, 1), Ok(()));
+            assert_eq!(erc721.user_of(1), None);
+            assert_eq!(erc721.user_expires(1), 0);
+            // Mint, Approval, UpdateUser, UpdateUser and Transfer events
+            assert_eq!(5, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn attributes_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            assert_eq!(
+                erc721.set_attribute(1, b"level".to_vec(), vec![1]),
+                Err(Error::TokenNotFound)
+            );
+            // Alice issues TokenId = 1 to Bob and sets its attributes
+            assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            assert_eq!(erc721.set_attribute(1, b"level".to_vec(), vec![1]), Ok(()));
+            assert_eq!(erc721.set_attribute(1, b"class".to_vec(), b"mage".to_vec()), Ok(()));
+            assert_eq!(erc721.set_attribute(1, b"level".to_vec(), vec![2]), Ok(()));
+            assert_eq!(erc721.get_attribute(1, b"level".to_vec()), Some(vec![2]));
+            assert_eq!(erc721.get_attribute(1, b"class".to_vec()), Some(b"mage".to_vec()));
+            assert_eq!(erc721.get_attribute(1, b"name".to_vec()), None);
+            // Bob cannot set attributes
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc721.set_attribute(1, b"level".to_vec(), vec![99]),
+                Err(Error::NotContractOwner)
+            );
+            // Attributes are deleted on burn
             assert_eq!(erc721.burn(1), Ok(()));
-            // Check existence of TokenId = 1
-            assert_eq!(erc721.owner_of(1), None);
+            assert_eq!(erc721.get_attribute(1, b"level".to_vec()), None);
+            assert_eq!(erc721.get_attribute(1, b"class".to_vec()), None);
+            // Mint, three AttributeSet and burn events
+            assert_eq!(5, ink_env::test::recorded_events().count());
         }
 
         #[ink::test]
-        fn burn_fails_token_not_found() {
+        fn token_owner_attributes_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             // Create a new contract instance.
-            let mut erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 3);
-            // Check if burn fails. 
-            assert_eq!(erc721.burn(1), Err(Error::TokenNotFound));
+            let mut erc721 = new_erc721(50, Config { public_mint: false, ..config() });
+            assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            // Alice hands the attributes over to token owners
+            assert_eq!(erc721.set_token_owner_attributes(true), Ok(()));
+            assert!(erc721.token_owner_attributes());
+            assert_eq!(erc721.set_attribute(1, b"level".to_vec(), vec![1]), Err(Error::NotOwner));
+            set_caller(accounts.bob);
+            assert_eq!(erc721.set_token_owner_attributes(false), Err(Error::NotContractOwner));
+            assert_eq!(erc721.set_attribute(1, b"level".to_vec(), vec![1]), Ok(()));
+            assert_eq!(erc721.get_attribute(1, b"level".to_vec()), Some(vec![1]));
+            // The number of attributes is capped
+            for key in 1..MAX_ATTRIBUTES {
+                assert_eq!(erc721.set_attribute(1, key.to_le_bytes().to_vec(), vec![]), Ok(()));
+            }
+            assert_eq!(
+                erc721.set_attribute(1, b"extra".to_vec(), vec![]),
+                Err(Error::TooManyAttributes)
+            );
+            assert_eq!(erc721.set_attribute(1, b"level".to_vec(), vec![2]), Ok(()));
         }
 
         #[ink::test]
-        fn burn_fails_not_owner() {
+        fn mint_with_id_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             // Create a new contract instance.
-            let mut erc721 = Erc721::new("Test Token".to_string(), "TEST".to_string(), 3);
-            // Alice mints a token
+            let mut erc721 = new_erc721(50, config());
+            // Alice mints a catalogue serial and an id past the supply to Bob
+            let serial = u128::MAX - 7;
+            assert_eq!(erc721.mint_with_id(accounts.bob, serial), Ok(()));
+            assert_eq!(erc721.mint_with_id(accounts.bob, 51), Ok(()));
+            assert_eq!(erc721.owner_of(serial), Some(accounts.bob));
+            assert_eq!(erc721.token_uri(serial), Some(format!("ipfs://test/{}", serial)));
+            // Taken ids are rejected
+            assert_eq!(erc721.mint_with_id(accounts.charlie, 51), Err(Error::TokenExists));
+            assert_eq!(erc721.total_minted(), 2);
+            // Eve cannot choose ids
+            set_caller(accounts.eve);
+            assert_eq!(erc721.mint_with_id(accounts.eve, 70), Err(Error::MintNotAllowed));
+        }
+
+        #[ink::test]
+        fn mint_with_id_keeps_sequential_ids_free() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(100, config());
             assert_eq!(erc721.mint(), Ok(()));
-            // Try burning this token with a different account
+            // Ids of the sequential range are reserved, wherever the cursor is
+            assert_eq!(erc721.mint_with_id(accounts.bob, 2), Err(Error::IdReserved));
+            assert_eq!(erc721.mint_with_id(accounts.bob, 100), Err(Error::IdReserved));
+            // Alice mints a long run of ids right past the supply
+            for id in 101..=160 {
+                assert_eq!(erc721.mint_with_id(accounts.bob, id), Ok(()));
+            }
+            assert_eq!(erc721.mint_with_id(accounts.bob, 0), Ok(()));
+            // Sequential mints keep going without collisions
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint_to(accounts.charlie), Ok(()));
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            assert_eq!(erc721.tokens_of_owner(accounts.alice), Ok(vec![1, 2, 4, 5]));
+            assert_eq!(erc721.owner_of(3), Some(accounts.charlie));
+            assert_eq!(erc721.total_minted(), 66);
+        }
+
+        #[ink::test]
+        fn psp34_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            assert_eq!(PSP34::total_supply(&erc721), 2);
+            assert_eq!(PSP34::balance_of(&erc721, accounts.alice), 2);
+            // Unsigned ids of any width refer to the same token
+            assert_eq!(PSP34::owner_of(&erc721, Id::U8(1)), Some(accounts.alice));
+            assert_eq!(PSP34::owner_of(&erc721, Id::U128(2)), Some(accounts.alice));
+            assert_eq!(PSP34::owner_of(&erc721, Id::U32(3)), None);
+            assert_eq!(PSP34::owner_of(&erc721, Id::Bytes(vec![1])), None);
+            // Alice approves Bob for TokenId = 1 and then revokes it
+            assert_eq!(PSP34::approve(&mut erc721, accounts.bob, Some(Id::U8(1)), true), Ok(()));
+            assert!(PSP34::allowance(&erc721, accounts.alice, accounts.bob, Some(Id::U8(1))));
+            assert!(!PSP34::allowance(&erc721, accounts.alice, accounts.bob, Some(Id::U8(2))));
+            assert!(!PSP34::allowance(&erc721, accounts.alice, accounts.bob, None));
+            assert_eq!(PSP34::approve(&mut erc721, accounts.bob, Some(Id::U8(1)), false), Ok(()));
+            assert!(!PSP34::allowance(&erc721, accounts.alice, accounts.bob, Some(Id::U8(1))));
+            // Alice sets Bob as an operator
+            assert_eq!(PSP34::approve(&mut erc721, accounts.bob, None, true), Ok(()));
+            assert!(PSP34::allowance(&erc721, accounts.alice, accounts.bob, Some(Id::U8(2))));
+            // Alice transfers TokenId = 2 to Charlie
+            assert_eq!(PSP34::transfer(&mut erc721, accounts.charlie, Id::U8(2), vec![]), Ok(()));
+            assert_eq!(erc721.owner_of(2), Some(accounts.charlie));
+            assert_eq!(PSP34::balance_of(&erc721, accounts.charlie), 1);
+            // Alice transfers TokenId = 1 to Django, a contract that accepts tokens
+            set_receiver(accounts.django, true);
+            assert_eq!(PSP34::transfer(&mut erc721, accounts.django, Id::U8(1), vec![1]), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.django));
+        }
+
+        #[ink::test]
+        fn psp34_errors_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(
+                PSP34::approve(&mut erc721, accounts.alice, Some(Id::U8(1)), true),
+                Err(PSP34Error::SelfApprove)
+            );
+            assert_eq!(
+                PSP34::transfer(&mut erc721, accounts.bob, Id::U8(2), vec![]),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(
+                PSP34::transfer(&mut erc721, accounts.bob, Id::Bytes(vec![1]), vec![]),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(erc721.pause(), Ok(()));
+            assert_eq!(
+                PSP34::transfer(&mut erc721, accounts.bob, Id::U8(1), vec![]),
+                Err(PSP34Error::Custom("Paused".to_string()))
+            );
+            assert_eq!(erc721.unpause(), Ok(()));
+            // Eve cannot move or approve Alice's token
             set_caller(accounts.eve);
-            assert_eq!(erc721.burn(1), Err(Error::NotOwner));
+            assert_eq!(
+                PSP34::transfer(&mut erc721, accounts.eve, Id::U8(1), vec![]),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(
+                PSP34::approve(&mut erc721, accounts.bob, Some(Id::U8(1)), false),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(
+                PSP34::approve(&mut erc721, accounts.bob, Some(Id::U8(1)), true),
+                Err(PSP34Error::NotApproved)
+            );
+            // Contracts that reject the token cannot receive it
+            set_caller(accounts.alice);
+            set_receiver(accounts.charlie, false);
+            assert_eq!(
+                PSP34::transfer(&mut erc721, accounts.charlie, Id::U8(1), vec![]),
+                Err(PSP34Error::SafeTransferCheckFailed("rejected".to_string()))
+            );
+        }
+
+        #[ink::test]
+        fn bulk_queries_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint_batch(vec![accounts.alice, accounts.bob, accounts.alice]), Ok(()));
+            assert_eq!(
+                erc721.owners_of(vec![1, 2, 4]),
+                Ok(vec![Some(accounts.alice), Some(accounts.bob), None])
+            );
+            assert_eq!(
+                erc721.balances_of(vec![accounts.alice, accounts.bob, accounts.eve]),
+                Ok(vec![2, 1, 0])
+            );
+            // Pages stop at the end of the token list
+            assert_eq!(erc721.tokens(0, 2), Ok(vec![1, 2]));
+            assert_eq!(erc721.tokens(2, 2), Ok(vec![3]));
+            assert_eq!(erc721.tokens(3, 2), Ok(vec![]));
+            assert_eq!(erc721.tokens(u32::MAX, 2), Ok(vec![]));
+            // Burning TokenId = 1 moves the last token into its slot
+            assert_eq!(erc721.burn(1), Ok(()));
+            assert_eq!(erc721.tokens(0, 10), Ok(vec![3, 2]));
+        }
+
+        #[ink::test]
+        fn bulk_queries_too_large_fail() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let erc721 = new_erc721(50, config());
+            let too_many = MAX_QUERY_LENGTH as usize + 1;
+            assert_eq!(erc721.owners_of(vec![1; too_many]), Err(Error::QueryTooLarge));
+            assert_eq!(erc721.balances_of(vec![accounts.alice; too_many]), Err(Error::QueryTooLarge));
+            assert_eq!(erc721.tokens(0, too_many as u32), Err(Error::QueryTooLarge));
+            assert_eq!(erc721.tokens(0, MAX_QUERY_LENGTH), Ok(vec![]));
+        }
+
+        #[ink::test]
+        fn tokens_of_owner_too_large_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(MAX_QUERY_LENGTH + 1, config());
+            for _ in 0..MAX_QUERY_LENGTH {
+                assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            }
+            assert_eq!(erc721.tokens_of_owner(accounts.bob).map(|ids| ids.len()), Ok(MAX_QUERY_LENGTH as usize));
+            // Larger holders must page with `token_of_owner_by_index`
+            assert_eq!(erc721.mint_to(accounts.bob), Ok(()));
+            assert_eq!(erc721.tokens_of_owner(accounts.bob), Err(Error::QueryTooLarge));
+            assert_eq!(erc721.token_of_owner_by_index(accounts.bob, MAX_QUERY_LENGTH), Some(MAX_QUERY_LENGTH as TokenId + 1));
+        }
+
+        #[ink::test]
+        fn lock_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            // Alice approves the staking contract Bob, which locks TokenId = 1 for two blocks
+            assert_eq!(erc721.set_approval_for_all(accounts.bob, true), Ok(()));
+            let until = ink_env::block_timestamp::<ink_env::DefaultEnvironment>() + 7;
+            set_caller(accounts.bob);
+            assert_eq!(erc721.lock(1, until), Ok(()));
+            assert_eq!(erc721.locked(1), Some(true));
+            assert!(erc721.is_locked(1));
+            assert_eq!(erc721.locked(2), Some(false));
+            assert!(!erc721.is_locked(2));
+            // Neither Alice nor Bob can move or burn it
+            assert_eq!(erc721.transfer_from(accounts.alice, accounts.bob, 1), Err(Error::TokenLocked));
+            set_caller(accounts.alice);
+            assert_eq!(erc721.transfer(accounts.eve, 1), Err(Error::TokenLocked));
+            assert_eq!(erc721.burn(1), Err(Error::TokenLocked));
+            assert_eq!(
+                erc721.safe_transfer_from(accounts.alice, accounts.eve, 1, vec![]),
+                Err(Error::TokenLocked)
+            );
+            // Batches including it are rejected as a whole
+            assert_eq!(
+                erc721.transfer_batch(vec![(accounts.eve, 2), (accounts.eve, 1)]),
+                Err(Error::TokenLocked)
+            );
+            assert_eq!(erc721.burn_batch(vec![2, 1]), Err(Error::TokenLocked));
+            assert_eq!(erc721.owner_of(2), Some(accounts.alice));
+            // Only Bob can unlock or extend it
+            assert_eq!(erc721.unlock(1), Err(Error::NotApproved));
+            assert_eq!(erc721.lock(1, until + 1), Err(Error::TokenLocked));
+            // The lock expires
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.locked(1), Some(true));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.locked(1), Some(false));
+            assert!(!erc721.is_locked(1));
+            assert_eq!(erc721.transfer(accounts.eve, 1), Ok(()));
+        }
+
+        #[ink::test]
+        fn unlock_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            assert_eq!(erc721.mint(), Ok(()));
+            let now = ink_env::block_timestamp::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.lock(1, now), Err(Error::NotAllowed));
+            assert_eq!(erc721.lock(2, now + 1), Err(Error::TokenNotFound));
+            assert_eq!(erc721.unlock(1), Err(Error::NotAllowed));
+            // Eve is not approved
+            set_caller(accounts.eve);
+            assert_eq!(erc721.lock(1, now + 1), Err(Error::NotApproved));
+            // Bob is approved for TokenId = 1 alone, which does not let him lock it
+            set_caller(accounts.alice);
+            assert_eq!(erc721.approve(accounts.bob, 1), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc721.lock(1, Timestamp::MAX), Err(Error::NotApproved));
+            // Alice locks TokenId = 1 and unlocks it early
+            set_caller(accounts.alice);
+            assert_eq!(erc721.lock(1, Timestamp::MAX), Ok(()));
+            assert_eq!(erc721.unlock(1), Ok(()));
+            assert!(!erc721.is_locked(1));
+            assert_eq!(erc721.burn(1), Ok(()));
+            // Mint, Approval, Locked, Unlocked, Burn events
+            assert_eq!(5, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn balance_of_at_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            let start = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            // Alice mints two tokens in the first block
+            assert_eq!(erc721.mint(), Ok(()));
+            assert_eq!(erc721.mint(), Ok(()));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            // Nothing happens in the second block
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            // Alice transfers TokenId = 1 to Bob in the third block
+            assert_eq!(erc721.transfer(accounts.bob, 1), Ok(()));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            // Bob burns it in the fourth block
+            set_caller(accounts.bob);
+            assert_eq!(erc721.burn(1), Ok(()));
+            // The fourth block is not finished yet
+            assert_eq!(erc721.balance_of_at(accounts.bob, start + 3), Err(Error::BlockNotFinished));
+            assert_eq!(erc721.balance_of_at(accounts.bob, start + 4), Err(Error::BlockNotFinished));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.balance_of_at(accounts.alice, start), Ok(2));
+            assert_eq!(erc721.balance_of_at(accounts.alice, start + 1), Ok(2));
+            assert_eq!(erc721.balance_of_at(accounts.alice, start + 2), Ok(1));
+            assert_eq!(erc721.balance_of_at(accounts.alice, start + 3), Ok(1));
+            assert_eq!(erc721.balance_of_at(accounts.bob, start + 1), Ok(0));
+            assert_eq!(erc721.balance_of_at(accounts.bob, start + 2), Ok(1));
+            assert_eq!(erc721.balance_of_at(accounts.bob, start + 3), Ok(0));
+            assert_eq!(erc721.balance_of_at(accounts.eve, start + 3), Ok(0));
+        }
+
+        #[ink::test]
+        fn delegate_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            let start = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.mint_batch(vec![accounts.alice; 2]), Ok(()));
+            // Tokens without delegate carry no votes
+            assert_eq!(erc721.get_votes(accounts.alice), 0);
+            // Alice delegates to herself, Bob delegates to Charlie
+            assert_eq!(erc721.delegate(accounts.alice), Ok(()));
+            assert_eq!(erc721.delegates(accounts.alice), Some(accounts.alice));
+            assert_eq!(erc721.get_votes(accounts.alice), 2);
+            set_caller(accounts.bob);
+            assert_eq!(erc721.delegate(accounts.charlie), Ok(()));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            // Alice transfers TokenId = 1 to Bob, its vote moves to Charlie
+            set_caller(accounts.alice);
+            assert_eq!(erc721.transfer(accounts.bob, 1), Ok(()));
+            assert_eq!(erc721.get_votes(accounts.alice), 1);
+            assert_eq!(erc721.get_votes(accounts.charlie), 1);
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            // Alice moves her remaining vote to Charlie
+            assert_eq!(erc721.delegate(accounts.charlie), Ok(()));
+            assert_eq!(erc721.get_votes(accounts.alice), 0);
+            assert_eq!(erc721.get_votes(accounts.charlie), 2);
+            // Past votes are only known for finished blocks
+            assert_eq!(erc721.get_past_votes(accounts.charlie, start + 2), Err(Error::BlockNotFinished));
+            assert_eq!(erc721.get_past_votes(accounts.alice, start), Ok(2));
+            assert_eq!(erc721.get_past_votes(accounts.alice, start + 1), Ok(1));
+            assert_eq!(erc721.get_past_votes(accounts.charlie, start), Ok(0));
+            assert_eq!(erc721.get_past_votes(accounts.charlie, start + 1), Ok(1));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc721.get_past_votes(accounts.charlie, start + 2), Ok(2));
+            assert_eq!(erc721.get_votes(accounts.charlie), 2);
+        }
+
+        /// Account of the secp256k1 key that signed the permit test vectors, the
+        /// Blake2x256 hash of its compressed public key.
+        const PERMIT_OWNER: [u8; 32] = [0x28, 0x37, 0xb0, 0x0a, 0xd6, 0xee, 0xf4, 0xb4, 0xab, 0x0c, 0xca, 0x77, 0x02, 0xe9, 0x99, 0xc6, 0xb2, 0x20, 0x4e, 0x45, 0x35, 0x14, 0x20, 0xa1, 0xf8, 0x2f, 0xf1, 0x0b, 0x24, 0xd0, 0x67, 0xfa];
+
+        /// Signatures generated off-chain by `PERMIT_OWNER` approving Bob for
+        /// TokenId = 1 of contract `[0x7; 32]` until timestamp 6, with nonce 0 and 1.
+        const PERMIT_SIGNATURES: [[u8; 65]; 2] = [
+            [0x1e, 0xd7, 0xfc, 0x50, 0x34, 0x4a, 0x92, 0x47, 0x37, 0xc4, 0x59, 0xf1, 0xb5, 0x3f, 0xb2, 0x9e, 0xe6, 0x89, 0x86, 0x0b, 0xb0, 0xb3, 0x6a, 0xb4, 0x9c, 0xfa, 0x63, 0x28, 0x42, 0x06, 0x95, 0xd8, 0x44, 0x74, 0x10, 0x80, 0x0e, 0x74, 0x22, 0xcb, 0xe1, 0xf0, 0x75, 0x18, 0x42, 0x60, 0x87, 0xcb, 0x36, 0x79, 0x83, 0x97, 0x7d, 0x76, 0x55, 0x4d, 0xcb, 0x1b, 0xb2, 0x8a, 0x0c, 0x6d, 0x11, 0xff, 0x00],
+            [0x43, 0xdf, 0x8b, 0x88, 0x6f, 0x40, 0x9f, 0x1f, 0xcd, 0xd6, 0xf7, 0x6c, 0x0d, 0x70, 0xbf, 0xf4, 0x8b, 0xa8, 0x05, 0x41, 0xd1, 0x50, 0x7b, 0x6d, 0x25, 0x4a, 0x66, 0xf3, 0xf2, 0xef, 0xfb, 0xca, 0x41, 0x1d, 0x6c, 0x2a, 0xbe, 0x9a, 0x90, 0x43, 0xca, 0xbf, 0x31, 0x50, 0x7f, 0xe9, 0xc7, 0xac, 0xed, 0xc3, 0x43, 0x7e, 0xb8, 0x9e, 0x69, 0xe4, 0xcb, 0xed, 0x98, 0xc7, 0x9a, 0x43, 0x0a, 0x28, 0x01],
+        ];
+
+        #[ink::test]
+        fn permit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(AccountId::from([0x7; 32]));
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            let owner = AccountId::from(PERMIT_OWNER);
+            assert_eq!(erc721.mint_to(owner), Ok(()));
+            assert_eq!(erc721.nonces(owner), 0);
+            // Charlie relays the permit of the owner
+            set_caller(accounts.charlie);
+            assert_eq!(erc721.permit(owner, accounts.bob, 1, 6, PERMIT_SIGNATURES[0]), Ok(()));
+            assert_eq!(erc721.get_approved(1), Some(accounts.bob));
+            assert_eq!(erc721.nonces(owner), 1);
+            // The same permit cannot be replayed
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, 6, PERMIT_SIGNATURES[0]),
+                Err(Error::InvalidSignature)
+            );
+            // The permit signed with the next nonce is accepted
+            assert_eq!(erc721.permit(owner, accounts.bob, 1, 6, PERMIT_SIGNATURES[1]), Ok(()));
+            assert_eq!(erc721.nonces(owner), 2);
+            // Bob transfers the owner's token to himself
+            set_caller(accounts.bob);
+            assert_eq!(erc721.transfer_from(owner, accounts.bob, 1), Ok(()));
+            assert_eq!(erc721.owner_of(1), Some(accounts.bob));
+            // Mint, Approval, Approval, Transfer events
+            assert_eq!(4, ink_env::test::recorded_events().count());
+        }
+
+        #[ink::test]
+        fn permit_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(AccountId::from([0x7; 32]));
+            // Create a new contract instance.
+            let mut erc721 = new_erc721(50, config());
+            let owner = AccountId::from(PERMIT_OWNER);
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, 6, PERMIT_SIGNATURES[0]),
+                Err(Error::TokenNotFound)
+            );
+            assert_eq!(erc721.mint_to(owner), Ok(()));
+            // The signature does not cover Charlie, a later deadline or another signer
+            assert_eq!(
+                erc721.permit(owner, accounts.charlie, 1, 6, PERMIT_SIGNATURES[0]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, 7, PERMIT_SIGNATURES[0]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, 6, [0x0; 65]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                erc721.permit(accounts.alice, accounts.bob, 1, 6, PERMIT_SIGNATURES[0]),
+                Err(Error::NotOwner)
+            );
+            // The permit expires after the deadline
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc721.permit(owner, accounts.bob, 1, 6, PERMIT_SIGNATURES[0]),
+                Err(Error::PermitExpired)
+            );
+            assert_eq!(erc721.get_approved(1), None);
+            assert_eq!(erc721.nonces(owner), 0);
+        }
+
+        /// Returns the allowlist leaf of `account`.
+        fn leaf(account: AccountId, max_allowed: u32) -> [u8; 32] {
+            let mut leaf = [0u8; 32];
+            ink_env::hash_encoded::<Keccak256, _>(&(account, max_allowed), &mut leaf);
+            leaf
+        }
+
+        /// Builds the Merkle tree of `leaves` and returns its root and the proof
+        /// of the leaf at `index`. A node without sibling moves up a level as is.
+        fn merkle_root_and_proof(leaves: &[[u8; 32]], mut index: usize) -> ([u8; 32], Vec<[u8; 32]>) {
+            let mut level = leaves.to_vec();
+            let mut proof = Vec::new();
+            while level.len() > 1 {
+                let sibling = index ^ 1;
+                if sibling < level.len() {
+                    proof.push(level[sibling]);
+                }
+                level = level
+                    .chunks(2)
+                    .map(|pair| {
+                        match pair {
+                            [left, right] => {
+                                let mut parent = [0u8; 32];
+                                let (a, b) = if left <= right { (left, right) } else { (right, left) };
+                                ink_env::hash_bytes::<Keccak256>(&[*a, *b].concat(), &mut parent);
+                                parent
+                            }
+                            [node] => *node,
+                            _ => unreachable!(),
+                        }
+                    })
+                    .collect();
+                index /= 2;
+            }
+            (level[0], proof)
+        }
+
+        fn set_value_transferred(value: Balance) {
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(value);
+        }
+
+        /// Registers `account` as a receiver contract that accepts or rejects tokens.
+        fn set_receiver(account: AccountId, accepts: bool) {
+            RECEIVERS.with(|receivers| receivers.borrow_mut().insert(account, accepts));
+        }
+
+        /// Returns the answer of `to` to `on_erc721_received`, plain accounts
+        /// are not callable.
+        pub(super) fn receiver_answer(
+            to: &AccountId,
+        ) -> Result<Result<(), ReceiverError>, ink_env::Error> {
+            match RECEIVERS.with(|receivers| receivers.borrow().get(to).copied()) {
+                Some(true) => Ok(Ok(())),
+                Some(false) => Ok(Err(ReceiverError::TransferRejected)),
+                None => Err(ink_env::Error::NotCallable),
+            }
         }
 
         fn set_caller(sender: AccountId) {
             ink_env::test::set_caller::<ink_env::DefaultEnvironment>(sender);
         }
+
+        /// Returns a config with public mint, a 5% royalty to Alice, no
+        /// provenance hash and transferable tokens.
+        fn config() -> Config {
+            Config {
+                public_mint: true,
+                royalty: (AccountId::from([0x1; 32]), 500),
+                provenance_hash: None,
+                soulbound: false,
+            }
+        }
+
+        /// Creates a contract with `max_supply` tokens under `ipfs://test/`.
+        fn new_erc721(max_supply: u32, config: Config) -> Erc721 {
+            Erc721::new("Test Token".to_string(), "TEST".to_string(), max_supply, "ipfs://test/".to_string(), config)
+        }
     }
 }
@@ -9,6 +9,10 @@ pub mod psp22template {
         burnable::*,
         metadata::*,
     };
+    use brush::contracts::access_control::*;
+    use brush::modifiers;
+    use brush::traits::Flush;
+    use ink_env::CallFlags;
     use ink_storage::traits::SpreadAllocate;
     use ink_lang::codegen::{Env, EmitEvent};
 
@@ -30,14 +34,24 @@ pub mod psp22template {
         value: Balance,
     }
 
+    /// Role allowed to mint tokens.
+    pub const MINTER: RoleType = ink_lang::selector_id!("MINTER");
+
+    /// Role allowed to burn its own tokens or an allowance.
+    pub const BURNER: RoleType = ink_lang::selector_id!("BURNER");
+
     #[ink(storage)]
-    #[derive(Default, SpreadAllocate, PSP22Storage, PSP22MetadataStorage)]
+    #[derive(Default, SpreadAllocate, PSP22Storage, PSP22MetadataStorage, AccessControlStorage)]
     pub struct PSP22Template {
         count: u32,
         #[PSP22StorageField]
         psp22: PSP22Data,
         #[PSP22MetadataStorageField]
         metadata: PSP22MetadataData,
+        #[AccessControlStorageField]
+        access: AccessControlData,
+        /// Maximum total supply, set at init
+        cap: Balance,
     }
 
     impl PSP22Transfer for PSP22Template {
@@ -47,7 +61,14 @@ pub mod psp22template {
             _to: Option<&AccountId>,
             _amount: &Balance,
         ) -> Result<(), PSP22Error> {
-             Ok(())
+            // Every mint path goes through here with no sender
+            if _from.is_none() {
+                let supply = self.total_supply().checked_add(*_amount);
+                if supply.map_or(true, |supply| supply > self.cap) {
+                    return Err(PSP22Error::Custom(String::from("Cap exceeded")))
+                }
+            }
+            Ok(())
         }
 
         fn _after_token_transfer(
@@ -76,14 +97,14 @@ pub mod psp22template {
 
         fn _emit_approval_event(
             &self,
-            _owner: Option<AccountId>,
-            _spender: Option<AccountId>,
+            _owner: AccountId,
+            _spender: AccountId,
             _amount: Balance,
         ) {
             self.env().emit_event(Approval {
                 owner: _owner,
-                operator: _operator,
-                amount: _amount,
+                spender: _spender,
+                value: _amount,
             });
         }
 
@@ -93,7 +114,13 @@ pub mod psp22template {
             _to: &AccountId,
             _value: &Balance,
             _data: &Vec<u8>,
-        ) -> Result<(), PSP22Error> {}
+        ) -> Result<(), PSP22Error> {
+            // Storage must be up to date if the receiver calls back
+            self.flush();
+            let result = self.call_receiver(_from, _to, _value, _data);
+            self.load();
+            receiver_result(result)
+        }
     }
 
     impl PSP22 for PSP22Template {
@@ -110,8 +137,42 @@ pub mod psp22template {
          */
     }
 
-    impl PSP22Mintable for PSP22Template { /* mint() */ }
-    impl PSP22Burnable for PSP22Template { /* burn() */ }
+    impl PSP22Mintable for PSP22Template {
+        #[ink(message)]
+        #[modifiers(only_role(MINTER))]
+        fn mint(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+            self._mint(account, amount)
+        }
+    }
+
+    impl PSP22Burnable for PSP22Template {
+        /// Burns `amount` of the caller's tokens, or of `account`'s tokens
+        /// within the caller's allowance.
+        #[ink(message)]
+        #[modifiers(only_role(BURNER))]
+        fn burn(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            if account != caller {
+                let allowance = self.allowance(account, caller);
+                if allowance < amount {
+                    return Err(PSP22Error::InsufficientAllowance)
+                }
+                self._approve_from_to(account, caller, allowance - amount)?;
+            }
+            self._burn_from(account, amount)
+        }
+    }
+
+    impl AccessControl for PSP22Template {
+        /*
+         * has_role()
+         * get_role_admin()
+         * grant_role()
+         * revoke_role()
+         * renounce_role()
+         *
+         */
+    }
    
     impl PSP22Metadata for PSP22Template { 
         /* 
@@ -124,13 +185,63 @@ pub mod psp22template {
 
     impl PSP22Template {
         #[ink(constructor)]
-        pub fn new(name: Option<String>, symbol: Option<String>, decimal: u8) -> Self {
+        pub fn new(name: Option<String>, symbol: Option<String>, decimal: u8, cap: Balance) -> Self {
             ink_lang::codegen::initialize_contract(|_instance: &mut Self| {
                 _instance.metadata.name = name;
                 _instance.metadata.symbol = symbol;
                 _instance.metadata.decimals = decimal;
+                _instance.cap = cap;
+
+                let caller = _instance.env().caller();
+                _instance._init_with_admin(caller);
+                _instance.grant_role(MINTER, caller).expect("Should grant MINTER role");
+                _instance.grant_role(BURNER, caller).expect("Should grant BURNER role");
             })
         }
+
+        /// Returns the maximum total supply.
+        #[ink(message)]
+        pub fn cap(&self) -> Balance {
+            self.cap
+        }
+
+        /// Calls `PSP22Receiver::before_received` on the `to` contract.
+        fn call_receiver(
+            &mut self,
+            from: &AccountId,
+            to: &AccountId,
+            value: &Balance,
+            data: &[u8],
+        ) -> Result<Result<(), PSP22ReceiverError>, ink_env::Error> {
+            PSP22ReceiverRef::before_received_builder(
+                to,
+                self.env().caller(),
+                *from,
+                *value,
+                data.to_vec(),
+            )
+            .call_flags(CallFlags::default().set_allow_reentry(true))
+            .fire()
+        }
+    }
+
+    /// Maps the answer of the receiver to the result of the transfer, plain
+    /// accounts are not callable and always receive the tokens.
+    fn receiver_result(
+        result: Result<Result<(), PSP22ReceiverError>, ink_env::Error>,
+    ) -> Result<(), PSP22Error> {
+        match result {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(PSP22ReceiverError::TransferRejected(reason))) => {
+                Err(PSP22Error::SafeTransferCheckFailed(reason))
+            }
+            Err(ink_env::Error::NotCallable) => Ok(()),
+            Err(_) => {
+                Err(PSP22Error::SafeTransferCheckFailed(String::from(
+                    "Error during call to receiver",
+                )))
+            }
+        }
     }
 
     #[cfg(test)]
@@ -144,17 +255,18 @@ pub mod psp22template {
         #[ink::test]
         fn init_works() {
             // Create a new contract instance
-            let psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18); 
+            let psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000); 
             // Check metadata fields
             assert_eq!(psp22.token_name(), Some(String::from("TestToken")));
             assert_eq!(psp22.token_symbol(), Some(String::from("TEST")));
             assert_eq!(psp22.token_decimals(), 18);
+            assert_eq!(psp22.cap(), 1_000_000);
         }
 
         #[ink::test]
         fn mint_to_zero_address_fails() {
             // Create a new contract instance
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let supply = 1000;
             // Attempt mint to zero address
             assert_eq!(
@@ -166,7 +278,7 @@ pub mod psp22template {
         #[ink::test]
         fn total_supply_works() {
             // Create a new contract instance
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_mint = 1000;
             // Alice mints some tokens
@@ -184,7 +296,7 @@ pub mod psp22template {
         #[ink::test]
         fn balance_of_works() {
             // Create a new contract instance
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_mint = 1000;
             // Alice mints some tokens
@@ -200,7 +312,7 @@ pub mod psp22template {
         #[ink::test]
         fn total_supply_increases_after_minting() {
             // Create a new contract instance
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
 
             // Alice's balance before minting
@@ -216,7 +328,7 @@ pub mod psp22template {
         #[ink::test]
         fn should_emit_transfer_event_after_mint() {
             // Create a new contract instance
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_mint = 1000;
             // Alice mints some tokens
@@ -234,7 +346,7 @@ pub mod psp22template {
 
         #[ink::test]
         fn burn_no_balance_fails() {
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_burn = 1000;
             // Burn should fail, empty wallet
@@ -246,7 +358,7 @@ pub mod psp22template {
 
         #[ink::test]
         fn should_emit_transfer_event_after_burn() {
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_mint = 1000;
             let amount_to_burn = 300;
@@ -280,7 +392,7 @@ pub mod psp22template {
 
         #[ink::test]
         fn total_supply_decreases_after_burning() {
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_mint = 1000;
             let amount_to_burn = 250;
@@ -297,7 +409,7 @@ pub mod psp22template {
 
         #[ink::test]
         fn burn_works() {
-            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18);
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
             let accounts = brush::test_utils::accounts();
             let amount_to_mint = 1000;
             let amount_to_burn = 340;
@@ -312,6 +424,181 @@ pub mod psp22template {
             assert_eq!(psp22.balance_of(accounts.alice), amount_to_mint - amount_to_burn);
         }
 
+        #[ink::test]
+        fn should_emit_approval_event_after_approve() {
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
+            let accounts = brush::test_utils::accounts();
+            // Alice approves Bob
+            assert!(psp22.approve(accounts.bob, 500).is_ok());
+            assert_eq!(psp22.allowance(accounts.alice, accounts.bob), 500);
+            // Alice raises Bob's allowance
+            assert!(psp22.increase_allowance(accounts.bob, 100).is_ok());
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            assert_approval_event(&emitted_events[0], accounts.alice, accounts.bob, 500);
+            assert_approval_event(&emitted_events[1], accounts.alice, accounts.bob, 600);
+        }
+
+        #[test]
+        fn receiver_result_works() {
+            // Plain accounts and accepting contracts receive the tokens
+            assert_eq!(receiver_result(Err(ink_env::Error::NotCallable)), Ok(()));
+            assert_eq!(receiver_result(Ok(Ok(()))), Ok(()));
+            // The reason of a rejection is passed through
+            assert_eq!(
+                receiver_result(Ok(Err(PSP22ReceiverError::TransferRejected(String::from("rejected"))))),
+                Err(PSP22Error::SafeTransferCheckFailed(String::from("rejected")))
+            );
+            // Failed calls reject the transfer
+            assert_eq!(
+                receiver_result(Err(ink_env::Error::CalleeTrapped)),
+                Err(PSP22Error::SafeTransferCheckFailed(String::from("Error during call to receiver")))
+            );
+        }
+
+        #[ink::test]
+        fn mint_at_cap_works() {
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1000);
+            let accounts = brush::test_utils::accounts();
+            assert!(psp22.mint(accounts.alice, 600).is_ok());
+            // Minting up to the cap is allowed
+            assert!(psp22.mint(accounts.bob, 400).is_ok());
+            assert_eq!(psp22.total_supply(), psp22.cap());
+            // Burned tokens free room under the cap
+            assert!(psp22.burn(accounts.alice, 100).is_ok());
+            assert!(psp22.mint(accounts.alice, 100).is_ok());
+            assert_eq!(psp22.total_supply(), 1000);
+        }
+
+        #[ink::test]
+        fn mint_over_cap_fails() {
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1000);
+            let accounts = brush::test_utils::accounts();
+            assert_eq!(
+                psp22.mint(accounts.alice, 1001),
+                Err(PSP22Error::Custom(String::from("Cap exceeded")))
+            );
+            assert!(psp22.mint(accounts.alice, 1000).is_ok());
+            assert_eq!(
+                psp22.mint(accounts.bob, 1),
+                Err(PSP22Error::Custom(String::from("Cap exceeded")))
+            );
+            assert_eq!(
+                psp22.mint(accounts.bob, Balance::MAX),
+                Err(PSP22Error::Custom(String::from("Cap exceeded")))
+            );
+            assert_eq!(psp22.total_supply(), 1000);
+        }
+
+        #[ink::test]
+        fn roles_granted_to_deployer() {
+            let psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
+            let accounts = brush::test_utils::accounts();
+            assert!(psp22.has_role(MINTER, accounts.alice));
+            assert!(psp22.has_role(BURNER, accounts.alice));
+            assert!(!psp22.has_role(MINTER, accounts.bob));
+            assert!(!psp22.has_role(BURNER, accounts.bob));
+        }
+
+        #[ink::test]
+        fn unauthorized_mint_fails() {
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
+            let accounts = brush::test_utils::accounts();
+            // Bob is not a minter
+            change_caller(accounts.bob);
+            assert_eq!(
+                psp22.mint(accounts.bob, 1000),
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            assert_eq!(psp22.total_supply(), 0);
+            // Alice grants Bob the minter role
+            change_caller(accounts.alice);
+            assert!(psp22.grant_role(MINTER, accounts.bob).is_ok());
+            change_caller(accounts.bob);
+            assert!(psp22.mint(accounts.bob, 1000).is_ok());
+            assert_eq!(psp22.balance_of(accounts.bob), 1000);
+        }
+
+        #[ink::test]
+        fn unauthorized_burn_fails() {
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
+            let accounts = brush::test_utils::accounts();
+            assert!(psp22.mint(accounts.bob, 1000).is_ok());
+            // Bob is not a burner, not even of his own tokens
+            change_caller(accounts.bob);
+            assert_eq!(
+                psp22.burn(accounts.bob, 100),
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            // Alice is a burner but has no allowance from Bob
+            change_caller(accounts.alice);
+            assert_eq!(
+                psp22.burn(accounts.bob, 100),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+            assert_eq!(psp22.balance_of(accounts.bob), 1000);
+        }
+
+        #[ink::test]
+        fn burn_from_allowance_works() {
+            let mut psp22 = PSP22Template::new(Some(String::from("TestToken")), Some(String::from("TEST")), 18, 1_000_000);
+            let accounts = brush::test_utils::accounts();
+            assert!(psp22.mint(accounts.bob, 1000).is_ok());
+            // Bob allows Alice to burn 300 of his tokens
+            change_caller(accounts.bob);
+            assert!(psp22.approve(accounts.alice, 300).is_ok());
+            change_caller(accounts.alice);
+            assert!(psp22.burn(accounts.bob, 200).is_ok());
+            assert_eq!(psp22.balance_of(accounts.bob), 800);
+            assert_eq!(psp22.allowance(accounts.bob, accounts.alice), 100);
+            assert_eq!(
+                psp22.burn(accounts.bob, 200),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+        }
+
+        fn assert_approval_event(
+            event: &ink_env::test::EmittedEvent,
+            expected_owner: AccountId,
+            expected_spender: AccountId,
+            expected_value: Balance,
+        ) {
+            let decoded_event = <Event as scale::Decode>::decode(&mut &event.data[..])
+                .expect("encountered invalid contract event data buffer");
+            if let Event::Approval(Approval { owner, spender, value }) = decoded_event {
+                assert_eq!(owner, expected_owner, "encountered invalid Approval.owner");
+                assert_eq!(spender, expected_spender, "encountered invalid Approval.spender");
+                assert_eq!(value, expected_value, "encountered invalid Approval.value");
+            } else {
+                panic!("encountered unexpected event kind: expected an Approval event")
+            }
+
+            let expected_topics = vec![
+                encoded_into_hash(&PrefixedValue {
+                    value: b"PSP22Template::Approval",
+                    prefix: b"",
+                }),
+                encoded_into_hash(&PrefixedValue {
+                    prefix: b"PSP22Template::Approval::owner",
+                    value: &expected_owner,
+                }),
+                encoded_into_hash(&PrefixedValue {
+                    prefix: b"PSP22Template::Approval::spender",
+                    value: &expected_spender,
+                }),
+            ];
+
+            for (n, (actual_topic, expected_topic)) in event.topics.iter().zip(expected_topics).enumerate() {
+                assert_eq!(
+                    &actual_topic[..],
+                    expected_topic.as_ref(),
+                    "encountered invalid topic as {}",
+                    n
+                );
+            }
+        }
+
         fn assert_transfer_event(
             event: &ink_env::test::EmittedEvent,
             expected_from: Option<AccountId>,
@@ -320,10 +607,13 @@ pub mod psp22template {
         ) {
             let decoded_event = <Event as scale::Decode>::decode(&mut &event.data[..])
                 .expect("encountered invalid contract event data buffer");
-            let Event::Transfer(Transfer { from, to, value }) = decoded_event;
-            assert_eq!(from, expected_from, "encountered invalid Transfer.from");
-            assert_eq!(to, expected_to, "encountered invalid Transfer.to");
-            assert_eq!(value, expected_value, "encountered invalid Transfer.value");
+            if let Event::Transfer(Transfer { from, to, value }) = decoded_event {
+                assert_eq!(from, expected_from, "encountered invalid Transfer.from");
+                assert_eq!(to, expected_to, "encountered invalid Transfer.to");
+                assert_eq!(value, expected_value, "encountered invalid Transfer.value");
+            } else {
+                panic!("encountered unexpected event kind: expected a Transfer event")
+            }
 
             let expected_topics = vec![
                 encoded_into_hash(&PrefixedValue {